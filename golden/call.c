@@ -0,0 +1 @@
+int add(int a, int b) { return a+b; } int main() { int x=1; return add(x, 2); }
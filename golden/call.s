@@ -0,0 +1,92 @@
+  .global add
+  .type add, @function
+add:
+  # a -> -4(fp)
+  # b -> -8(fp)
+  # push fp
+  addi sp, sp, -8
+  sd fp, 0(sp)
+  mv fp, sp
+  addi sp, sp, -16
+  sw a0, -4(fp)
+  sw a1, -8(fp)
+  addi a0, fp, -4
+  lw a0, 0(a0)
+  # push a0
+  addi sp, sp, -8
+  sd a0, 0(sp)
+  addi a0, fp, -8
+  lw a0, 0(a0)
+  # push a0
+  addi sp, sp, -8
+  sd a0, 0(sp)
+  # pop t1
+  ld t1, 0(sp)
+  addi sp, sp, 8
+  # pop t0
+  ld t0, 0(sp)
+  addi sp, sp, 8
+  add a0, t0, t1
+  j .L.return.add
+.L.return.add:
+  mv sp, fp
+  # pop fp
+  ld fp, 0(sp)
+  addi sp, sp, 8
+  ret
+  .size add, .-add
+  .global main
+  .type main, @function
+main:
+  # x -> -4(fp)
+  # push ra
+  addi sp, sp, -8
+  sd ra, 0(sp)
+  # push fp
+  addi sp, sp, -8
+  sd fp, 0(sp)
+  mv fp, sp
+  addi sp, sp, -16
+  addi a0, fp, -4
+  # push a0
+  addi sp, sp, -8
+  sd a0, 0(sp)
+  li a0, 1
+  # push a0
+  addi sp, sp, -8
+  sd a0, 0(sp)
+  # pop t0
+  ld t0, 0(sp)
+  addi sp, sp, 8
+  # pop t1
+  ld t1, 0(sp)
+  addi sp, sp, 8
+  sw t0, 0(t1)
+  lw a0, 0(t1)
+  addi a0, fp, -4
+  lw a0, 0(a0)
+  # push a0
+  addi sp, sp, -8
+  sd a0, 0(sp)
+  li a0, 2
+  # push a0
+  addi sp, sp, -8
+  sd a0, 0(sp)
+  # pop a1
+  ld a1, 0(sp)
+  addi sp, sp, 8
+  # pop a0
+  ld a0, 0(sp)
+  addi sp, sp, 8
+  call add
+  j .L.return.main
+.L.return.main:
+  mv sp, fp
+  # pop fp
+  ld fp, 0(sp)
+  addi sp, sp, 8
+  # pop ra
+  ld ra, 0(sp)
+  addi sp, sp, 8
+  ret
+  .size main, .-main
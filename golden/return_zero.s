@@ -0,0 +1,8 @@
+  .global main
+  .type main, @function
+main:
+  li a0, 0
+  j .L.return.main
+.L.return.main:
+  ret
+  .size main, .-main
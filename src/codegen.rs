@@ -1,143 +1,467 @@
-use std::collections::HashMap;
+use std::io::{self, Write};
 
 use crate::{
-    ctype::{TypedFunction, TypedNode, TypedNodeKind},
-    parser::BinOp,
+    ctype::{CType, TypedFunction, TypedNode, TypedNodeKind, TypedProgram},
+    parser::{BinOp, GlobalVar},
 };
 
-pub struct Codegen<'src> {
-    locals: HashMap<&'src str, i32>,
+#[derive(Default)]
+pub struct CodegenOptions {
+    /// Set up `fp` in every function, even when the frame is empty.
+    pub frame_pointer: bool,
+    /// Disable linker relaxation for generated functions.
+    pub no_relax: bool,
+    /// Zero every local slot in the prologue.
+    pub zero_init_locals: bool,
+}
+
+pub struct Codegen<'src, W: Write> {
+    out: W,
+    options: CodegenOptions,
+    function_name: &'src str,
     count: usize,
+    depth: usize,
+    /// Label numbers of the loops enclosing the current statement
+    loops: Vec<usize>,
+    /// String literals seen so far, emitted as `.L.str.N` after the code
+    strings: Vec<Vec<u8>>,
 }
 
-impl<'src> Codegen<'src> {
-    pub fn new() -> Self {
+impl<'src, W: Write> Codegen<'src, W> {
+    pub fn new(out: W, options: CodegenOptions) -> Self {
         Self {
-            locals: HashMap::new(),
+            out,
+            options,
+            function_name: "",
             count: 0,
+            depth: 0,
+            loops: vec![],
+            strings: vec![],
         }
     }
 
-    pub fn codegen(&mut self, function: TypedFunction<'src>) {
-        let mut offset = 0;
+    pub fn codegen(&mut self, program: TypedProgram<'src>) -> io::Result<()> {
+        if !program.globals.is_empty() {
+            self.gen_data(&program.globals)?;
+        }
+        for function in program.functions {
+            self.gen_function(function)?;
+        }
+        if !self.strings.is_empty() {
+            self.gen_strings()?;
+        }
+
+        Ok(())
+    }
 
-        for local in function.locals.into_iter().rev() {
-            offset += 8;
-            self.locals.insert(local, -(offset as i32));
+    /// Reserve zeroed storage for every global, then switch back to code.
+    fn gen_data(&mut self, globals: &[GlobalVar]) -> io::Result<()> {
+        writeln!(self.out, "  .bss")?;
+        for var in globals {
+            writeln!(self.out, "  .global {}", var.name)?;
+            writeln!(self.out, "  .type {}, @object", var.name)?;
+            writeln!(self.out, "  .size {}, {}", var.name, var.ctype.size())?;
+            writeln!(self.out, "  .balign {}", var.ctype.align())?;
+            writeln!(self.out, "{}:", var.name)?;
+            writeln!(self.out, "  .zero {}", var.ctype.size())?;
         }
-        let stack_size = align_to(offset, 16);
+        writeln!(self.out, "  .text")?;
 
-        println!("  .global main");
-        println!("main:");
+        Ok(())
+    }
+
+    fn gen_strings(&mut self) -> io::Result<()> {
+        writeln!(self.out, "  .section .rodata")?;
+        for (i, value) in self.strings.iter().enumerate() {
+            writeln!(self.out, ".L.str.{}:", i)?;
+            writeln!(self.out, "  .string \"{}\"", escape(value))?;
+        }
+
+        Ok(())
+    }
+
+    fn gen_function(&mut self, function: TypedFunction<'src>) -> io::Result<()> {
+        self.function_name = function.name;
+        let stack_size = function.frame_size;
+        // Nothing is addressed relative to fp when the frame is empty, and
+        // saving ra alongside fp keeps sp 16-byte aligned for calls
+        let use_fp = self.options.frame_pointer || stack_size > 0 || !function.is_leaf;
+
+        writeln!(self.out, "  .global {}", function.name)?;
+        writeln!(self.out, "  .type {}, @function", function.name)?;
+        if self.options.no_relax {
+            writeln!(self.out, "  .option norelax")?;
+        }
+        writeln!(self.out, "{}:", function.name)?;
 
         // Prologue
-        push("fp");
-        println!("  mv fp, sp");
-        println!("  addi sp, sp, -{}", stack_size);
+        for local in &function.locals {
+            writeln!(self.out, "  # {} -> {}(fp)", local.name, local.offset)?;
+        }
+        if use_fp {
+            if !function.is_leaf {
+                writeln!(self.out, "  # push ra")?;
+                writeln!(self.out, "  addi sp, sp, -8")?;
+                writeln!(self.out, "  sd ra, 0(sp)")?;
+            }
+            writeln!(self.out, "  # push fp")?;
+            writeln!(self.out, "  addi sp, sp, -8")?;
+            writeln!(self.out, "  sd fp, 0(sp)")?;
+            writeln!(self.out, "  mv fp, sp")?;
+            if is_imm12(-(stack_size as i64)) {
+                writeln!(self.out, "  addi sp, sp, -{}", stack_size)?;
+            } else {
+                writeln!(self.out, "  li t0, {}", stack_size)?;
+                writeln!(self.out, "  sub sp, sp, t0")?;
+            }
+        }
+        if self.options.zero_init_locals {
+            for offset in (8..=stack_size).step_by(8) {
+                if is_imm12(-(offset as i64)) {
+                    writeln!(self.out, "  sd zero, -{}(fp)", offset)?;
+                } else {
+                    writeln!(self.out, "  li t0, -{}", offset)?;
+                    writeln!(self.out, "  add t0, fp, t0")?;
+                    writeln!(self.out, "  sd zero, 0(t0)")?;
+                }
+            }
+        }
+        for (i, param) in function.locals[..function.params.len()].iter().enumerate() {
+            writeln!(
+                self.out,
+                "  s{} a{}, {}(fp)",
+                width(&param.ctype),
+                i,
+                param.offset
+            )?;
+        }
 
-        self.gen_stmt(function.node);
+        // Falling off the end of main returns 0
+        let falls_through = !matches!(
+            &function.node.kind,
+            TypedNodeKind::Block(nodes)
+                if matches!(nodes.last(), Some(TypedNode { kind: TypedNodeKind::Return(_), .. }))
+        );
+        self.gen_stmt(function.node)?;
+        if function.name == "main" && falls_through {
+            writeln!(self.out, "  li a0, 0")?;
+        }
 
         // Epilogue
-        println!(".L.return:");
-        println!("  mv sp, fp");
-        pop("fp");
+        writeln!(self.out, ".L.return.{}:", function.name)?;
+        if use_fp {
+            writeln!(self.out, "  mv sp, fp")?;
+            writeln!(self.out, "  # pop fp")?;
+            writeln!(self.out, "  ld fp, 0(sp)")?;
+            writeln!(self.out, "  addi sp, sp, 8")?;
+            if !function.is_leaf {
+                writeln!(self.out, "  # pop ra")?;
+                writeln!(self.out, "  ld ra, 0(sp)")?;
+                writeln!(self.out, "  addi sp, sp, 8")?;
+            }
+        }
 
-        println!("  ret");
+        writeln!(self.out, "  ret")?;
+        writeln!(self.out, "  .size {0}, .-{0}", function.name)?;
+        if self.options.no_relax {
+            writeln!(self.out, "  .option relax")?;
+        }
+
+        Ok(())
     }
 
-    fn gen_addr(&self, node: TypedNode) {
+    fn push(&mut self, reg: &str) -> io::Result<()> {
+        writeln!(self.out, "  # push {}", reg)?;
+        writeln!(self.out, "  addi sp, sp, -8")?;
+        writeln!(self.out, "  sd {}, 0(sp)", reg)?;
+        self.depth += 1;
+
+        Ok(())
+    }
+
+    fn pop(&mut self, reg: &str) -> io::Result<()> {
+        writeln!(self.out, "  # pop {}", reg)?;
+        writeln!(self.out, "  ld {}, 0(sp)", reg)?;
+        writeln!(self.out, "  addi sp, sp, 8")?;
+        self.depth -= 1;
+
+        Ok(())
+    }
+
+    fn gen_addr(&mut self, node: TypedNode) -> io::Result<()> {
         match node.kind {
-            TypedNodeKind::Var(name) => {
-                println!("  addi a0, fp, {}", self.locals.get(name).unwrap());
+            TypedNodeKind::Var(var) => {
+                if is_imm12(var.offset as i64) {
+                    writeln!(self.out, "  addi a0, fp, {}", var.offset)?;
+                } else {
+                    writeln!(self.out, "  li t0, {}", var.offset)?;
+                    writeln!(self.out, "  add a0, fp, t0")?;
+                }
+            }
+            TypedNodeKind::GlobalVar(var) => {
+                writeln!(self.out, "  la a0, {}", var.name)?;
+            }
+            TypedNodeKind::Str(value) => {
+                writeln!(self.out, "  la a0, .L.str.{}", self.strings.len())?;
+                self.strings.push(value);
+            }
+            TypedNodeKind::Func(name) => {
+                writeln!(self.out, "  la a0, {}", name)?;
             }
             TypedNodeKind::Deref(node) => {
-                self.gen_expr(*node);
+                self.gen_expr(*node)?;
             }
             _ => {
                 panic!("{:?} is not an lvalue", node);
             }
         }
+
+        Ok(())
     }
 
-    fn gen_expr(&self, node: TypedNode) {
+    fn gen_expr(&mut self, node: TypedNode) -> io::Result<()> {
         match node.kind {
             TypedNodeKind::Num(value) => {
-                println!("  li a0, {}", value);
+                writeln!(self.out, "  li a0, {}", value)?;
             }
-            TypedNodeKind::Var(_) => {
-                self.gen_addr(node);
-                println!("  ld a0, 0(a0)");
+            TypedNodeKind::Var(_)
+            | TypedNodeKind::GlobalVar(_)
+            | TypedNodeKind::Str(_)
+            | TypedNodeKind::Func(_) => {
+                let ctype = node.ctype.clone();
+                self.gen_addr(node)?;
+                self.load(&ctype)?;
             }
-            TypedNodeKind::Deref(node) => {
-                self.gen_expr(*node);
-                println!("  ld a0, 0(a0)");
+            TypedNodeKind::Deref(inner) => {
+                self.gen_expr(*inner)?;
+                self.load(&node.ctype)?;
             }
             TypedNodeKind::Addr(node) => {
-                self.gen_addr(*node);
+                self.gen_addr(*node)?;
+            }
+            TypedNodeKind::Cast(inner) => {
+                self.gen_expr(*inner)?;
+                // Zero- or sign-extend from the width of the target type
+                let bits = 64 - 8 * node.ctype.size();
+                if node.ctype == CType::UChar {
+                    writeln!(self.out, "  andi a0, a0, 255")?;
+                } else if bits > 0 {
+                    writeln!(self.out, "  slli a0, a0, {}", bits)?;
+                    writeln!(self.out, "  srai a0, a0, {}", bits)?;
+                }
+            }
+            TypedNodeKind::BitNot(node) => {
+                self.gen_expr(*node)?;
+                writeln!(self.out, "  not a0, a0")?;
+            }
+            TypedNodeKind::FuncCall { name, args } => {
+                let nargs = args.len();
+                for arg in args {
+                    self.gen_expr(arg)?;
+                    self.push("a0")?;
+                }
+                for i in (0..nargs).rev() {
+                    self.pop(&format!("a{}", i))?;
+                }
+
+                // sp must be 16-byte aligned at a call
+                if self.depth % 2 == 1 {
+                    writeln!(self.out, "  addi sp, sp, -8")?;
+                    writeln!(self.out, "  call {}", name)?;
+                    writeln!(self.out, "  addi sp, sp, 8")?;
+                } else {
+                    writeln!(self.out, "  call {}", name)?;
+                }
+            }
+            TypedNodeKind::Putchar(node) => {
+                self.gen_expr(*node)?;
+                self.push("a0")?;
+
+                // write(1, sp, 1)
+                writeln!(self.out, "  li a0, 1")?;
+                writeln!(self.out, "  mv a1, sp")?;
+                writeln!(self.out, "  li a2, 1")?;
+                writeln!(self.out, "  li a7, 64")?;
+                writeln!(self.out, "  ecall")?;
+
+                self.pop("a0")?;
+            }
+            TypedNodeKind::Getchar => {
+                writeln!(self.out, "  addi sp, sp, -8")?;
+
+                // read(0, sp, 1)
+                writeln!(self.out, "  li a0, 0")?;
+                writeln!(self.out, "  mv a1, sp")?;
+                writeln!(self.out, "  li a2, 1")?;
+                writeln!(self.out, "  li a7, 63")?;
+                writeln!(self.out, "  ecall")?;
+
+                // Return the byte read, or -1 if nothing was read
+                writeln!(self.out, "  lbu t0, 0(sp)")?;
+                writeln!(self.out, "  addi sp, sp, 8")?;
+                writeln!(self.out, "  addi a0, a0, -1")?;
+                writeln!(self.out, "  snez a0, a0")?;
+                writeln!(self.out, "  neg a0, a0")?;
+                writeln!(self.out, "  or a0, t0, a0")?;
+            }
+            TypedNodeKind::BinOp {
+                op: op @ (BinOp::LogAnd | BinOp::LogOr),
+                lhs,
+                rhs,
+            } => {
+                self.count += 1;
+                let count = self.count;
+
+                // Skip the rhs once the lhs decides the result
+                let (branch, short, full) = match op {
+                    BinOp::LogAnd => ("beq", 0, 1),
+                    _ => ("bne", 1, 0),
+                };
+                self.gen_expr(*lhs)?;
+                writeln!(self.out, "  {} a0, zero, .L.short.{}", branch, count)?;
+                self.gen_expr(*rhs)?;
+                writeln!(self.out, "  {} a0, zero, .L.short.{}", branch, count)?;
+                writeln!(self.out, "  li a0, {}", full)?;
+                writeln!(self.out, "  j .L.end.{}", count)?;
+                writeln!(self.out, ".L.short.{}:", count)?;
+                writeln!(self.out, "  li a0, {}", short)?;
+                writeln!(self.out, ".L.end.{}:", count)?;
+            }
+            TypedNodeKind::Cond { cond, then, els } => {
+                self.count += 1;
+                let count = self.count;
+
+                self.gen_cond(*cond, &format!(".L.else.{}", count))?;
+                self.gen_expr(*then)?;
+                writeln!(self.out, "  j .L.end.{}", count)?;
+                writeln!(self.out, ".L.else.{}:", count)?;
+                self.gen_expr(*els)?;
+                writeln!(self.out, ".L.end.{}:", count)?;
+            }
+            TypedNodeKind::BinOp {
+                op: BinOp::Comma,
+                lhs,
+                rhs,
+            } => {
+                self.gen_expr(*lhs)?;
+                self.gen_expr(*rhs)?;
             }
             TypedNodeKind::BinOp {
                 op: BinOp::Assign,
                 lhs,
                 rhs,
             } => {
-                self.gen_addr(*lhs);
-                push("a0");
+                let ctype = lhs.ctype.clone();
+                self.gen_addr(*lhs)?;
+                self.push("a0")?;
 
-                self.gen_expr(*rhs);
-                push("a0");
+                self.gen_expr(*rhs)?;
+                self.push("a0")?;
 
-                pop("t0");
-                pop("t1");
+                self.pop("t0")?;
+                self.pop("t1")?;
 
-                println!("  sd t0, 0(t1)");
-                println!("  mv a0, t0");
+                // Read the value back so it is narrowed to the lhs type
+                writeln!(self.out, "  s{} t0, 0(t1)", width(&ctype))?;
+                writeln!(self.out, "  {} a0, 0(t1)", load_op(&ctype))?;
             }
-            TypedNodeKind::BinOp { op, lhs, rhs } => {
-                self.gen_expr(*lhs);
-                push("a0");
-                self.gen_expr(*rhs);
-                push("a0");
-
-                pop("t1");
-                pop("t0");
-
-                match op {
-                    BinOp::Add => {
-                        println!("  add a0, t0, t1");
-                    }
-                    BinOp::Sub => {
-                        println!("  sub a0, t0, t1");
-                    }
-                    BinOp::Mul => {
-                        println!("  mul a0, t0, t1");
-                    }
-                    BinOp::Div => {
-                        println!("  div a0, t0, t1");
-                    }
-                    BinOp::Eq => {
-                        println!("  xor a0, t0, t1");
-                        println!("  sltiu a0, a0, 1");
-                    }
-                    BinOp::Ne => {
-                        println!("  xor a0, t0, t1");
-                        println!("  snez a0, a0");
-                    }
-                    BinOp::Lt => {
-                        println!("  slt a0, t0, t1");
-                    }
-                    BinOp::Le => {
-                        println!("  slt a0, t1, t0");
-                        println!("  xori a0, a0, 1");
-                    }
-                    _ => unreachable!(),
+            TypedNodeKind::AssignOp { op, lhs, rhs } => {
+                let ctype = lhs.ctype.clone();
+                self.gen_addr(*lhs)?;
+                self.push("a0")?;
+                self.gen_expr(*rhs)?;
+                self.push("a0")?;
+
+                self.pop("t1")?;
+                self.pop("t2")?;
+
+                writeln!(self.out, "  {} t0, 0(t2)", load_op(&ctype))?;
+                self.gen_arith(&op)?;
+                writeln!(self.out, "  s{} a0, 0(t2)", width(&ctype))?;
+                writeln!(self.out, "  {} a0, 0(t2)", load_op(&ctype))?;
+            }
+            // x * 2^k is a shift, and x * (2^k + 1) a shift and an add
+            TypedNodeKind::BinOp {
+                op: BinOp::Mul,
+                lhs,
+                rhs,
+            } if shift_add(&lhs).or(shift_add(&rhs)).is_some() => {
+                let (node, (shift, add)) = match shift_add(&rhs) {
+                    Some(multiplier) => (*lhs, multiplier),
+                    None => (*rhs, shift_add(&lhs).unwrap()),
+                };
+                self.gen_expr(node)?;
+
+                if add {
+                    writeln!(self.out, "  slli t0, a0, {}", shift)?;
+                    writeln!(self.out, "  add a0, t0, a0")?;
+                } else {
+                    writeln!(self.out, "  slli a0, a0, {}", shift)?;
                 }
             }
+            TypedNodeKind::BinOp {
+                op: BinOp::Div,
+                lhs,
+                rhs,
+            } if matches!(rhs.kind, TypedNodeKind::Num(value) if value.unsigned_abs() >= 2) => {
+                let TypedNodeKind::Num(divisor) = rhs.kind else {
+                    unreachable!()
+                };
+                self.gen_expr(*lhs)?;
+                self.gen_div_const(divisor)?;
+            }
+            TypedNodeKind::BinOp { op, lhs, rhs } => {
+                self.gen_expr(*lhs)?;
+                self.push("a0")?;
+                self.gen_expr(*rhs)?;
+                self.push("a0")?;
+
+                self.pop("t1")?;
+                self.pop("t0")?;
+
+                self.gen_arith(&op)?;
+            }
 
             _ => panic!("invalid expression"),
         }
+
+        Ok(())
     }
 
-    fn gen_stmt(&mut self, node: TypedNode) {
+    /// Jump to `label` if `cond` is false. A comparison branches on its
+    /// operands directly instead of materializing 0 or 1 first.
+    fn gen_cond(&mut self, cond: TypedNode, label: &str) -> io::Result<()> {
+        let TypedNodeKind::BinOp {
+            op: op @ (BinOp::Eq | BinOp::Ne | BinOp::Lt | BinOp::Le),
+            lhs,
+            rhs,
+        } = cond.kind
+        else {
+            self.gen_expr(cond)?;
+            writeln!(self.out, "  beq a0, zero, {}", label)?;
+            return Ok(());
+        };
+
+        self.gen_expr(*lhs)?;
+        self.push("a0")?;
+        self.gen_expr(*rhs)?;
+        self.push("a0")?;
+
+        self.pop("t1")?;
+        self.pop("t0")?;
+
+        match op {
+            BinOp::Eq => writeln!(self.out, "  bne t0, t1, {}", label)?,
+            BinOp::Ne => writeln!(self.out, "  beq t0, t1, {}", label)?,
+            BinOp::Lt => writeln!(self.out, "  bge t0, t1, {}", label)?,
+            BinOp::Le => writeln!(self.out, "  blt t1, t0, {}", label)?,
+            _ => unreachable!(),
+        }
+
+        Ok(())
+    }
+
+    fn gen_stmt(&mut self, node: TypedNode) -> io::Result<()> {
         match node.kind {
             TypedNodeKind::For {
                 init,
@@ -146,66 +470,273 @@ impl<'src> Codegen<'src> {
                 then,
             } => {
                 self.count += 1;
+                let count = self.count;
                 if let Some(init) = init {
-                    self.gen_stmt(*init);
+                    self.gen_stmt(*init)?;
                 }
-                println!(".L.begin.{}:", self.count);
+                writeln!(self.out, ".L.begin.{}:", count)?;
                 if let Some(cond) = cond {
-                    self.gen_expr(*cond);
-                    println!("  beq a0, zero, .L.end.{}", self.count);
+                    self.gen_cond(*cond, &format!(".L.end.{}", count))?;
                 }
-                self.gen_stmt(*then);
+                self.loops.push(count);
+                self.gen_stmt(*then)?;
+                self.loops.pop();
+                writeln!(self.out, ".L.continue.{}:", count)?;
                 if let Some(inc) = inc {
-                    self.gen_expr(*inc);
+                    self.gen_expr(*inc)?;
                 }
-                println!("  j .L.begin.{}", self.count);
-                println!(".L.end.{}:", self.count);
+                writeln!(self.out, "  j .L.begin.{}", count)?;
+                writeln!(self.out, ".L.end.{}:", count)?;
+            }
+            TypedNodeKind::DoWhile { then, cond } => {
+                self.count += 1;
+                let count = self.count;
+                writeln!(self.out, ".L.begin.{}:", count)?;
+                self.loops.push(count);
+                self.gen_stmt(*then)?;
+                self.loops.pop();
+                writeln!(self.out, ".L.continue.{}:", count)?;
+                self.gen_cond(*cond, &format!(".L.end.{}", count))?;
+                writeln!(self.out, "  j .L.begin.{}", count)?;
+                writeln!(self.out, ".L.end.{}:", count)?;
             }
             TypedNodeKind::If { cond, then, els } => {
                 self.count += 1;
+                let count = self.count;
 
-                self.gen_expr(*cond);
-                println!("  beq a0, zero, .L.else.{}", self.count);
+                self.gen_cond(*cond, &format!(".L.else.{}", count))?;
 
-                self.gen_stmt(*then);
-                println!("  j .L.end.{}", self.count);
-                println!(".L.else.{}:", self.count);
+                self.gen_stmt(*then)?;
+                writeln!(self.out, "  j .L.end.{}", count)?;
+                writeln!(self.out, ".L.else.{}:", count)?;
                 if let Some(els) = els {
-                    self.gen_stmt(*els);
+                    self.gen_stmt(*els)?;
                 }
-                println!(".L.end.{}:", self.count);
+                writeln!(self.out, ".L.end.{}:", count)?;
             }
             TypedNodeKind::Block(nodes) => {
                 for node in nodes {
-                    self.gen_stmt(node);
+                    self.gen_stmt(node)?;
                 }
             }
             TypedNodeKind::Return(node) => {
-                self.gen_expr(*node);
-                println!("  j .L.return");
+                self.gen_expr(*node)?;
+                writeln!(self.out, "  j .L.return.{}", self.function_name)?;
+            }
+            TypedNodeKind::Break => {
+                writeln!(self.out, "  j .L.end.{}", self.loops.last().unwrap())?;
+            }
+            TypedNodeKind::Continue => {
+                writeln!(self.out, "  j .L.continue.{}", self.loops.last().unwrap())?;
             }
             TypedNodeKind::ExprStmt(node) => {
-                self.gen_expr(*node);
+                self.gen_expr(*node)?;
             }
             _ => {
                 panic!("invalid statement");
             }
         }
+
+        Ok(())
+    }
+
+    fn load(&mut self, ctype: &CType) -> io::Result<()> {
+        // An array's or function's value is its address, so there is nothing to load
+        if let CType::Array(..) | CType::Func(_) = ctype {
+            return Ok(());
+        }
+
+        writeln!(self.out, "  {} a0, 0(a0)", load_op(ctype))?;
+        Ok(())
+    }
+
+    /// Divide a0 by a constant, rounding toward zero, by multiplying with a
+    /// fixed-point reciprocal instead of using `div`.
+    fn gen_div_const(&mut self, divisor: i32) -> io::Result<()> {
+        let (magic, shift) = magic(divisor.unsigned_abs() as u64);
+        writeln!(self.out, "  li t1, {}", magic)?;
+        writeln!(self.out, "  mulh t1, a0, t1")?;
+        // A magic number past i64::MAX was read as negative, so add the
+        // dividend back in
+        if magic < 0 {
+            writeln!(self.out, "  add t1, t1, a0")?;
+        }
+        if shift > 0 {
+            writeln!(self.out, "  srai t1, t1, {}", shift)?;
+        }
+        // The shift rounded a negative quotient down; round it toward zero
+        writeln!(self.out, "  srli t0, t1, 63")?;
+        writeln!(self.out, "  add a0, t1, t0")?;
+        if divisor < 0 {
+            writeln!(self.out, "  neg a0, a0")?;
+        }
+
+        Ok(())
+    }
+
+    /// Compute `t0 op t1` into a0.
+    fn gen_arith(&mut self, op: &BinOp) -> io::Result<()> {
+        match op {
+            BinOp::Add => {
+                writeln!(self.out, "  add a0, t0, t1")?;
+            }
+            BinOp::Sub => {
+                writeln!(self.out, "  sub a0, t0, t1")?;
+            }
+            BinOp::Mul => {
+                writeln!(self.out, "  mul a0, t0, t1")?;
+            }
+            BinOp::Div => {
+                writeln!(self.out, "  div a0, t0, t1")?;
+            }
+            BinOp::Mod => {
+                writeln!(self.out, "  rem a0, t0, t1")?;
+            }
+            BinOp::Eq => {
+                writeln!(self.out, "  xor a0, t0, t1")?;
+                writeln!(self.out, "  sltiu a0, a0, 1")?;
+            }
+            BinOp::Ne => {
+                writeln!(self.out, "  xor a0, t0, t1")?;
+                writeln!(self.out, "  snez a0, a0")?;
+            }
+            BinOp::Lt => {
+                writeln!(self.out, "  slt a0, t0, t1")?;
+            }
+            BinOp::Le => {
+                writeln!(self.out, "  slt a0, t1, t0")?;
+                writeln!(self.out, "  xori a0, a0, 1")?;
+            }
+            BinOp::BitAnd => {
+                writeln!(self.out, "  and a0, t0, t1")?;
+            }
+            BinOp::BitOr => {
+                writeln!(self.out, "  or a0, t0, t1")?;
+            }
+            BinOp::BitXor => {
+                writeln!(self.out, "  xor a0, t0, t1")?;
+            }
+            BinOp::Shl => {
+                writeln!(self.out, "  sll a0, t0, t1")?;
+            }
+            BinOp::Shr => {
+                writeln!(self.out, "  sra a0, t0, t1")?;
+            }
+            _ => unreachable!(),
+        }
+        Ok(())
     }
 }
 
-fn align_to(n: usize, align: usize) -> usize {
-    n.div_ceil(align) * align
+/// The shift and whether to add the unshifted value back, if `node` is a
+/// constant multiplier that needs no `mul`.
+fn shift_add(node: &TypedNode) -> Option<(u32, bool)> {
+    let TypedNodeKind::Num(value) = node.kind else {
+        return None;
+    };
+
+    match value {
+        2.. if (value as u32).is_power_of_two() => Some((value.trailing_zeros(), false)),
+        3.. if ((value - 1) as u32).is_power_of_two() => Some(((value - 1).trailing_zeros(), true)),
+        _ => None,
+    }
 }
 
-fn push(reg: &str) {
-    println!("  # push {}", reg);
-    println!("  addi sp, sp, -8");
-    println!("  sd {}, 0(sp)", reg);
+/// The multiplier and shift that divide a signed 64-bit value by `divisor`
+/// (at least 2) with `mulh`, per Hacker's Delight, section 10-4.
+fn magic(divisor: u64) -> (i64, u32) {
+    let divisor = divisor as u128;
+    let two63 = 1u128 << 63;
+    let anc = two63 - 1 - two63 % divisor;
+
+    let mut p = 63;
+    let (mut q1, mut r1) = (two63 / anc, two63 % anc);
+    let (mut q2, mut r2) = (two63 / divisor, two63 % divisor);
+    loop {
+        p += 1;
+        q1 *= 2;
+        r1 *= 2;
+        if r1 >= anc {
+            q1 += 1;
+            r1 -= anc;
+        }
+        q2 *= 2;
+        r2 *= 2;
+        if r2 >= divisor {
+            q2 += 1;
+            r2 -= divisor;
+        }
+
+        let delta = divisor - r2;
+        if q1 > delta || (q1 == delta && r1 != 0) {
+            break;
+        }
+    }
+
+    ((q2 + 1) as u64 as i64, p - 64)
+}
+
+/// Whether `value` fits the 12-bit signed immediate of addi, ld and sd.
+fn is_imm12(value: i64) -> bool {
+    (-2048..=2047).contains(&value)
+}
+
+/// The load/store suffix for a scalar of this type.
+fn width(ctype: &CType) -> char {
+    match ctype.size() {
+        1 => 'b',
+        4 => 'w',
+        8 => 'd',
+        size => panic!("{} バイトの値は読み書きできません", size),
+    }
+}
+
+/// The load instruction for a scalar of this type, zero-extending an
+/// unsigned char.
+fn load_op(ctype: &CType) -> String {
+    let unsigned = if *ctype == CType::UChar { "u" } else { "" };
+    format!("l{}{}", width(ctype), unsigned)
 }
 
-fn pop(reg: &str) {
-    println!("  # pop {}", reg);
-    println!("  ld {}, 0(sp)", reg);
-    println!("  addi sp, sp, 8");
+/// Quote the bytes of a string for `.string`.
+fn escape(value: &[u8]) -> String {
+    let mut escaped = String::new();
+    for &byte in value {
+        match byte {
+            b'\n' => escaped.push_str("\\n"),
+            b'\t' => escaped.push_str("\\t"),
+            b'\\' => escaped.push_str("\\\\"),
+            b'"' => escaped.push_str("\\\""),
+            b' '..=b'~' => escaped.push(byte as char),
+            byte => escaped.push_str(&format!("\\{:03o}", byte)),
+        }
+    }
+
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        ctype::type_program,
+        lexer::Lexer,
+        parser::{Parser, ParserOptions},
+    };
+
+    #[test]
+    fn writes_to_any_sink() {
+        let source = "int main() { return 0; }";
+        let tokens = Lexer::new(source).lex().unwrap();
+        let program = Parser::new(source, tokens, ParserOptions::default())
+            .parse()
+            .unwrap();
+
+        let mut out = Vec::<u8>::new();
+        Codegen::new(&mut out, CodegenOptions::default())
+            .codegen(type_program(program).unwrap())
+            .unwrap();
+
+        assert!(String::from_utf8(out).unwrap().contains("main:"));
+    }
 }
@@ -1,9 +1,24 @@
-use crate::parser::{BinOp, Function, Node, NodeKind};
+use std::ops::Range;
+
+use crate::{
+    error::CompileError,
+    parser::{BinOp, Function, GlobalVar, LocalVar, Node, NodeKind, Program},
+};
+
+#[derive(Debug)]
+pub struct TypedProgram<'src> {
+    pub globals: Vec<GlobalVar<'src>>,
+    pub functions: Vec<TypedFunction<'src>>,
+}
 
 #[derive(Debug)]
 pub struct TypedFunction<'src> {
+    pub name: &'src str,
+    pub params: Vec<&'src str>,
     pub node: TypedNode<'src>,
-    pub locals: Vec<&'src str>,
+    pub locals: Vec<LocalVar<'src>>,
+    pub frame_size: usize,
+    pub is_leaf: bool,
 }
 
 #[derive(Debug, PartialEq, Eq, Clone)]
@@ -15,84 +30,274 @@ pub struct TypedNode<'src> {
 #[derive(Debug, PartialEq, Eq, Clone)]
 pub enum TypedNodeKind<'src> {
     Num(i32),
+    Str(Vec<u8>),
     ExprStmt(Box<TypedNode<'src>>),
-    Var(&'src str),
+    Var(LocalVar<'src>),
+    GlobalVar(GlobalVar<'src>),
+    Func(&'src str),
     Return(Box<TypedNode<'src>>),
+    Break,
+    Continue,
     Block(Vec<TypedNode<'src>>),
     Addr(Box<TypedNode<'src>>),
     Deref(Box<TypedNode<'src>>),
+    BitNot(Box<TypedNode<'src>>),
+    Cast(Box<TypedNode<'src>>),
+    Putchar(Box<TypedNode<'src>>),
+    Getchar,
+    FuncCall {
+        name: &'src str,
+        args: Vec<TypedNode<'src>>,
+    },
     If {
         cond: Box<TypedNode<'src>>,
         then: Box<TypedNode<'src>>,
         els: Option<Box<TypedNode<'src>>>,
     },
+    Cond {
+        cond: Box<TypedNode<'src>>,
+        then: Box<TypedNode<'src>>,
+        els: Box<TypedNode<'src>>,
+    },
     For {
         init: Option<Box<TypedNode<'src>>>,
         cond: Option<Box<TypedNode<'src>>>,
         inc: Option<Box<TypedNode<'src>>>,
         then: Box<TypedNode<'src>>,
     },
+    DoWhile {
+        then: Box<TypedNode<'src>>,
+        cond: Box<TypedNode<'src>>,
+    },
     BinOp {
         op: BinOp,
         lhs: Box<TypedNode<'src>>,
         rhs: Box<TypedNode<'src>>,
     },
+    AssignOp {
+        op: BinOp,
+        lhs: Box<TypedNode<'src>>,
+        rhs: Box<TypedNode<'src>>,
+    },
 }
 
 #[derive(Debug, PartialEq, Eq, Clone)]
 pub enum CType {
+    /// A plain or signed char, which sign-extends when loaded
+    Char,
+    UChar,
     Int,
     Ptr(Box<CType>),
+    Array(Box<CType>, usize),
+    /// A function returning the given type
+    Func(Box<CType>),
     Statement,
 }
 
-pub fn type_function(function: Function) -> TypedFunction {
-    TypedFunction {
-        node: type_node(function.node),
-        locals: function.locals,
+impl CType {
+    pub fn size(&self) -> usize {
+        match self {
+            CType::Char | CType::UChar => 1,
+            CType::Int => 4,
+            CType::Ptr(_) => 8,
+            CType::Array(base, len) => base.size() * len,
+            CType::Func(_) => panic!("関数にはサイズがありません"),
+            CType::Statement => panic!("文にはサイズがありません"),
+        }
+    }
+
+    /// Whether values of the type occupy storage, unlike a function.
+    pub fn has_size(&self) -> bool {
+        match self {
+            CType::Array(base, _) => base.has_size(),
+            CType::Func(_) | CType::Statement => false,
+            _ => true,
+        }
+    }
+
+    pub fn align(&self) -> usize {
+        match self {
+            CType::Array(base, _) => base.align(),
+            ctype => ctype.size(),
+        }
+    }
+
+    /// An array used as a value is a pointer to its first element, and a
+    /// function is a pointer to itself.
+    pub fn decay(&self) -> CType {
+        match self {
+            CType::Array(base, _) => CType::Ptr(base.clone()),
+            CType::Func(_) => CType::Ptr(Box::new(self.clone())),
+            ctype => ctype.clone(),
+        }
+    }
+}
+
+/// The type an operand has in arithmetic, after decaying and widening
+/// anything narrower than int.
+fn arith_type(ctype: &CType) -> CType {
+    match ctype.decay() {
+        CType::Char | CType::UChar => CType::Int,
+        ctype => ctype,
     }
 }
 
-fn type_node(node: Node) -> TypedNode {
-    match node.kind {
+pub fn type_of(node: Node) -> Result<CType, CompileError> {
+    Ok(type_node(node)?.ctype)
+}
+
+fn error<T>(message: &str, span: Range<usize>) -> Result<T, CompileError> {
+    Err(CompileError {
+        message: message.to_string(),
+        span,
+    })
+}
+
+/// The size of what a pointer of type `ctype` points to, as the constant
+/// node its offsets are scaled by.
+fn scale(base: &CType, span: &Range<usize>) -> Result<TypedNode<'static>, CompileError> {
+    if let CType::Func(_) = base {
+        return error("関数へのポインタは加減算できません", span.clone());
+    }
+
+    Ok(TypedNode {
+        kind: TypedNodeKind::Num(base.size() as i32),
+        ctype: CType::Int,
+    })
+}
+
+fn is_lvalue(node: &TypedNode) -> bool {
+    matches!(
+        node.kind,
+        TypedNodeKind::Var(_) | TypedNodeKind::GlobalVar(_) | TypedNodeKind::Deref(_)
+    )
+}
+
+pub fn type_program(program: Program) -> Result<TypedProgram, CompileError> {
+    Ok(TypedProgram {
+        globals: program.globals,
+        functions: program
+            .functions
+            .into_iter()
+            .map(type_function)
+            .collect::<Result<_, _>>()?,
+    })
+}
+
+fn type_function(function: Function) -> Result<TypedFunction, CompileError> {
+    Ok(TypedFunction {
+        frame_size: function.frame_size(),
+        name: function.name,
+        params: function.params,
+        node: type_node(function.node)?,
+        locals: function.locals,
+        is_leaf: function.is_leaf,
+    })
+}
+
+fn type_boxed(node: Node) -> Result<Box<TypedNode>, CompileError> {
+    Ok(Box::new(type_node(node)?))
+}
+
+fn type_node(node: Node) -> Result<TypedNode, CompileError> {
+    let span = node.span;
+    let typed_node = match node.kind {
         NodeKind::Num(value) => TypedNode {
             kind: TypedNodeKind::Num(value),
             ctype: CType::Int,
         },
-        NodeKind::Var(name) => TypedNode {
-            kind: TypedNodeKind::Var(name),
-            ctype: CType::Int,
+        // The terminating null byte is part of the array
+        NodeKind::Str(value) => TypedNode {
+            ctype: CType::Array(Box::new(CType::Char), value.len() + 1),
+            kind: TypedNodeKind::Str(value),
         },
-        NodeKind::BinOp {
-            op: op @ (BinOp::Eq | BinOp::Ne | BinOp::Le | BinOp::Lt),
-            lhs,
-            rhs,
-        } => {
-            let lhs = type_node(*lhs);
-            let rhs = type_node(*rhs);
+        // The operand is only typed, never evaluated
+        NodeKind::Sizeof(node) => {
+            let ctype = type_node(*node)?.ctype;
+            if !ctype.has_size() {
+                return error("関数に sizeof は適用できません", span);
+            }
 
             TypedNode {
-                kind: TypedNodeKind::BinOp {
-                    op,
-                    lhs: Box::new(lhs),
-                    rhs: Box::new(rhs),
-                },
+                kind: TypedNodeKind::Num(ctype.size() as i32),
                 ctype: CType::Int,
             }
         }
+        NodeKind::Var(var) => TypedNode {
+            ctype: var.ctype.clone(),
+            kind: TypedNodeKind::Var(var),
+        },
+        NodeKind::GlobalVar(var) => TypedNode {
+            ctype: var.ctype.clone(),
+            kind: TypedNodeKind::GlobalVar(var),
+        },
+        // Every function returns int for now
+        NodeKind::Func(name) => TypedNode {
+            kind: TypedNodeKind::Func(name),
+            ctype: CType::Func(Box::new(CType::Int)),
+        },
+        NodeKind::BinOp {
+            op: op @ (BinOp::Eq | BinOp::Ne | BinOp::Le | BinOp::Lt | BinOp::LogAnd | BinOp::LogOr),
+            lhs,
+            rhs,
+        } => TypedNode {
+            kind: TypedNodeKind::BinOp {
+                op,
+                lhs: type_boxed(*lhs)?,
+                rhs: type_boxed(*rhs)?,
+            },
+            ctype: CType::Int,
+        },
         NodeKind::BinOp { op, lhs, rhs } => {
-            let lhs = type_node(*lhs);
-            let rhs = type_node(*rhs);
+            let lhs = type_node(*lhs)?;
+            let rhs = type_node(*rhs)?;
 
-            match (&op, lhs.ctype.clone(), rhs.ctype.clone()) {
-                (BinOp::Assign, lhs_ctype, _) => TypedNode {
+            match (&op, arith_type(&lhs.ctype), arith_type(&rhs.ctype)) {
+                // The lhs is evaluated only for its side effects, and the
+                // rhs keeps its own type rather than the promoted one
+                (BinOp::Comma, _, _) => TypedNode {
+                    ctype: rhs.ctype.decay(),
                     kind: TypedNodeKind::BinOp {
                         op,
                         lhs: Box::new(lhs),
                         rhs: Box::new(rhs),
                     },
-                    ctype: lhs_ctype.clone(),
                 },
+                (BinOp::Mul | BinOp::Div | BinOp::Mod, CType::Ptr(_), _)
+                | (BinOp::Mul | BinOp::Div | BinOp::Mod, _, CType::Ptr(_)) => {
+                    return error("ポインタは乗除算のオペランドにできません", span);
+                }
+                (
+                    BinOp::BitAnd | BinOp::BitOr | BinOp::BitXor | BinOp::Shl | BinOp::Shr,
+                    lhs_ctype,
+                    rhs_ctype,
+                ) if lhs_ctype != CType::Int || rhs_ctype != CType::Int => {
+                    return error("ビット演算のオペランドは整数でなければなりません", span);
+                }
+                (BinOp::Assign, lhs_ctype, rhs_ctype) => {
+                    if !is_lvalue(&lhs) {
+                        return error("左辺値が必要です", span);
+                    }
+                    if matches!(lhs.ctype, CType::Array(..)) {
+                        return error("配列には代入できません", span);
+                    }
+                    if matches!(lhs_ctype, CType::Ptr(_))
+                        && rhs_ctype == CType::Int
+                        && rhs.kind != TypedNodeKind::Num(0)
+                    {
+                        eprintln!("警告: キャストなしで整数をポインタに代入しています");
+                    }
+
+                    // The value has the type of the lhs, not its promoted type
+                    TypedNode {
+                        ctype: lhs.ctype.clone(),
+                        kind: TypedNodeKind::BinOp {
+                            op,
+                            lhs: Box::new(lhs),
+                            rhs: Box::new(rhs),
+                        },
+                    }
+                }
                 (_, CType::Int, CType::Int) => TypedNode {
                     kind: TypedNodeKind::BinOp {
                         op,
@@ -107,9 +312,7 @@ fn type_node(node: Node) -> TypedNode {
                         kind: TypedNodeKind::BinOp {
                             op: BinOp::Mul,
                             lhs: Box::new(lhs),
-                            rhs: Box::new(type_node(Node {
-                                kind: NodeKind::Num(8),
-                            })),
+                            rhs: Box::new(scale(&ctype, &span)?),
                         },
                         ctype: CType::Int,
                     };
@@ -129,9 +332,7 @@ fn type_node(node: Node) -> TypedNode {
                         kind: TypedNodeKind::BinOp {
                             op: BinOp::Mul,
                             lhs: Box::new(rhs),
-                            rhs: Box::new(type_node(Node {
-                                kind: NodeKind::Num(8),
-                            })),
+                            rhs: Box::new(scale(&ctype, &span)?),
                         },
                         ctype: CType::Int,
                     };
@@ -146,7 +347,7 @@ fn type_node(node: Node) -> TypedNode {
                     }
                 }
                 // ptr - ptr
-                (BinOp::Sub, CType::Ptr(_), CType::Ptr(_)) => {
+                (BinOp::Sub, CType::Ptr(ctype), CType::Ptr(_)) => {
                     let typed_node = TypedNode {
                         kind: TypedNodeKind::BinOp {
                             op,
@@ -160,28 +361,70 @@ fn type_node(node: Node) -> TypedNode {
                         kind: TypedNodeKind::BinOp {
                             op: BinOp::Div,
                             lhs: Box::new(typed_node),
-                            rhs: Box::new(type_node(Node {
-                                kind: NodeKind::Num(8),
-                            })),
+                            rhs: Box::new(scale(&ctype, &span)?),
                         },
                         ctype: CType::Int,
                     }
                 }
+                (BinOp::Add, CType::Ptr(_), CType::Ptr(_)) => {
+                    return error("ポインタ同士の加算はできません", span);
+                }
+
+                _ => return error("演算子のオペランドの型が正しくありません", span),
+            }
+        }
+        NodeKind::AssignOp { op, lhs, rhs } => {
+            let lhs = type_node(*lhs)?;
+            let rhs = type_node(*rhs)?;
+
+            if !is_lvalue(&lhs) {
+                return error("左辺値が必要です", span);
+            }
 
-                (_, CType::Int, CType::Ptr(_))
-                | (_, CType::Statement, _)
-                | (_, _, CType::Statement)
-                | (_, CType::Ptr(_), CType::Int)
-                | (_, CType::Ptr(_), CType::Ptr(_)) => panic!("{:?} {:?} {:?}", lhs, op, rhs),
+            let rhs = match (&op, &lhs.ctype, arith_type(&rhs.ctype)) {
+                (_, CType::Array(..), _) => return error("配列には代入できません", span),
+                (BinOp::Mul | BinOp::Div | BinOp::Mod, CType::Ptr(_), _)
+                | (BinOp::Mul | BinOp::Div | BinOp::Mod, _, CType::Ptr(_)) => {
+                    return error("ポインタは乗除算のオペランドにできません", span);
+                }
+                (
+                    BinOp::BitAnd | BinOp::BitOr | BinOp::BitXor | BinOp::Shl | BinOp::Shr,
+                    lhs_ctype,
+                    rhs_ctype,
+                ) if arith_type(lhs_ctype) != CType::Int || rhs_ctype != CType::Int => {
+                    return error("ビット演算のオペランドは整数でなければなりません", span);
+                }
+                (_, CType::Char | CType::UChar | CType::Int, CType::Int) => rhs,
+                // ptr += int, ptr -= int
+                (BinOp::Add | BinOp::Sub, CType::Ptr(ctype), CType::Int) => TypedNode {
+                    kind: TypedNodeKind::BinOp {
+                        op: BinOp::Mul,
+                        lhs: Box::new(rhs),
+                        rhs: Box::new(scale(ctype, &span)?),
+                    },
+                    ctype: CType::Int,
+                },
+                _ => return error("演算子のオペランドの型が正しくありません", span),
+            };
+
+            TypedNode {
+                ctype: lhs.ctype.clone(),
+                kind: TypedNodeKind::AssignOp {
+                    op,
+                    lhs: Box::new(lhs),
+                    rhs: Box::new(rhs),
+                },
             }
         }
         NodeKind::Addr(node) => {
-            let typed_node = type_node(*node);
+            let typed_node = type_node(*node)?;
             let ctype = match typed_node.kind {
-                TypedNodeKind::Var(_) | TypedNodeKind::Deref(_) => {
-                    CType::Ptr(Box::new(typed_node.ctype.clone()))
-                }
-                _ => panic!(),
+                TypedNodeKind::Var(_)
+                | TypedNodeKind::GlobalVar(_)
+                | TypedNodeKind::Str(_)
+                | TypedNodeKind::Func(_)
+                | TypedNodeKind::Deref(_) => CType::Ptr(Box::new(typed_node.ctype.clone())),
+                _ => return error("左辺値が必要です", span),
             };
 
             TypedNode {
@@ -190,10 +433,12 @@ fn type_node(node: Node) -> TypedNode {
             }
         }
         NodeKind::Deref(node) => {
-            let typed_node = type_node(*node);
+            let typed_node = type_node(*node)?;
             let ctype = match &typed_node.ctype {
-                CType::Ptr(ctype) => *ctype.clone(),
-                _ => CType::Int,
+                CType::Ptr(ctype) | CType::Array(ctype, _) => *ctype.clone(),
+                // *f designates f itself
+                ctype @ CType::Func(_) => ctype.clone(),
+                _ => return error("ポインタではないものは参照外しできません", span),
             };
 
             TypedNode {
@@ -201,35 +446,108 @@ fn type_node(node: Node) -> TypedNode {
                 ctype,
             }
         }
-        NodeKind::ExprStmt(node) => {
-            let typed_node = Box::new(type_node(*node));
-            TypedNode {
-                kind: TypedNodeKind::ExprStmt(typed_node),
-                ctype: CType::Statement,
+        NodeKind::Cast(node, ctype) => TypedNode {
+            kind: TypedNodeKind::Cast(type_boxed(*node)?),
+            ctype,
+        },
+        NodeKind::BitNot(node) => {
+            let typed_node = type_node(*node)?;
+            if arith_type(&typed_node.ctype) != CType::Int {
+                return error("ビット演算のオペランドは整数でなければなりません", span);
             }
-        }
-        NodeKind::Return(node) => {
-            let typed_node = Box::new(type_node(*node));
+
             TypedNode {
-                kind: TypedNodeKind::Return(typed_node),
-                ctype: CType::Statement,
+                kind: TypedNodeKind::BitNot(Box::new(typed_node)),
+                ctype: CType::Int,
             }
         }
-        NodeKind::Block(nodes) => {
-            let typed_nodes = nodes.into_iter().map(type_node).collect::<Vec<_>>();
-            TypedNode {
-                kind: TypedNodeKind::Block(typed_nodes),
-                ctype: CType::Statement,
-            }
+        NodeKind::Putchar(node) => TypedNode {
+            kind: TypedNodeKind::Putchar(type_boxed(*node)?),
+            ctype: CType::Int,
+        },
+        NodeKind::Getchar => TypedNode {
+            kind: TypedNodeKind::Getchar,
+            ctype: CType::Int,
+        },
+        NodeKind::FuncCall { name, args } => TypedNode {
+            kind: TypedNodeKind::FuncCall {
+                name,
+                args: args.into_iter().map(type_node).collect::<Result<_, _>>()?,
+            },
+            ctype: CType::Int,
+        },
+        NodeKind::Generic {
+            control,
+            assocs,
+            default,
+        } => {
+            let control = type_node(*control)?;
+
+            let Some(node) = assocs
+                .into_iter()
+                .find(|(ctype, _)| *ctype == control.ctype.decay())
+                .map(|(_, node)| node)
+                .or(default.map(|node| *node))
+            else {
+                let message = format!("_Generic に {:?} に一致する型がありません", control.ctype);
+                return error(&message, span);
+            };
+            type_node(node)?
         }
-        NodeKind::If { cond, then, els } => {
-            let cond = Box::new(type_node(*cond));
-            let then = Box::new(type_node(*then));
-            let els = els.map(|node| Box::new(type_node(*node)));
+        NodeKind::ExprStmt(node) => TypedNode {
+            kind: TypedNodeKind::ExprStmt(type_boxed(*node)?),
+            ctype: CType::Statement,
+        },
+        NodeKind::Return(node) => TypedNode {
+            kind: TypedNodeKind::Return(type_boxed(*node)?),
+            ctype: CType::Statement,
+        },
+        NodeKind::Break => TypedNode {
+            kind: TypedNodeKind::Break,
+            ctype: CType::Statement,
+        },
+        NodeKind::Continue => TypedNode {
+            kind: TypedNodeKind::Continue,
+            ctype: CType::Statement,
+        },
+        NodeKind::Block(nodes) => TypedNode {
+            kind: TypedNodeKind::Block(nodes.into_iter().map(type_node).collect::<Result<_, _>>()?),
+            ctype: CType::Statement,
+        },
+        NodeKind::If { cond, then, els } => TypedNode {
+            kind: TypedNodeKind::If {
+                cond: type_boxed(*cond)?,
+                then: type_boxed(*then)?,
+                els: els.map(|node| type_boxed(*node)).transpose()?,
+            },
+            ctype: CType::Statement,
+        },
+        NodeKind::Cond { cond, then, els } => {
+            let cond = type_boxed(*cond)?;
+            let then = type_boxed(*then)?;
+            let els = type_boxed(*els)?;
+
+            let is_null = |node: &TypedNode| node.kind == TypedNodeKind::Num(0);
+            let ctype = match (arith_type(&then.ctype), arith_type(&els.ctype)) {
+                (CType::Int, CType::Int) => CType::Int,
+                (ptr @ CType::Ptr(_), CType::Int) if is_null(&els) => ptr,
+                (CType::Int, ptr @ CType::Ptr(_)) if is_null(&then) => ptr,
+                (lhs @ CType::Ptr(_), rhs @ CType::Ptr(_)) => {
+                    if lhs != rhs {
+                        eprintln!("警告: 条件演算子の2つのポインタの型が一致しません");
+                    }
+                    lhs
+                }
+                (CType::Ptr(base), _) | (_, CType::Ptr(base)) => {
+                    eprintln!("警告: 条件演算子でポインタと整数の型が一致しません");
+                    CType::Ptr(base)
+                }
+                _ => return error("条件演算子のオペランドの型が正しくありません", span),
+            };
 
             TypedNode {
-                kind: TypedNodeKind::If { cond, then, els },
-                ctype: CType::Statement,
+                ctype,
+                kind: TypedNodeKind::Cond { cond, then, els },
             }
         }
         NodeKind::For {
@@ -237,21 +555,91 @@ fn type_node(node: Node) -> TypedNode {
             cond,
             inc,
             then,
-        } => {
-            let init = init.map(|node| Box::new(type_node(*node)));
-            let cond = cond.map(|node| Box::new(type_node(*node)));
-            let inc = inc.map(|node| Box::new(type_node(*node)));
-            let then = Box::new(type_node(*then));
+        } => TypedNode {
+            kind: TypedNodeKind::For {
+                init: init.map(|node| type_boxed(*node)).transpose()?,
+                cond: cond.map(|node| type_boxed(*node)).transpose()?,
+                inc: inc.map(|node| type_boxed(*node)).transpose()?,
+                then: type_boxed(*then)?,
+            },
+            ctype: CType::Statement,
+        },
+        NodeKind::DoWhile { then, cond } => TypedNode {
+            kind: TypedNodeKind::DoWhile {
+                then: type_boxed(*then)?,
+                cond: type_boxed(*cond)?,
+            },
+            ctype: CType::Statement,
+        },
+    };
 
-            TypedNode {
-                kind: TypedNodeKind::For {
-                    init,
-                    cond,
-                    inc,
-                    then,
-                },
-                ctype: CType::Statement,
-            }
-        }
+    Ok(typed_node)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        lexer::Lexer,
+        parser::{Parser, ParserOptions},
+    };
+
+    fn type_source(source: &str) -> Result<TypedProgram<'_>, CompileError> {
+        let tokens = Lexer::new(source).lex().unwrap();
+        let program = Parser::new(source, tokens, ParserOptions::default())
+            .parse()
+            .unwrap();
+        type_program(program)
+    }
+
+    /// The type of the expression returned by the last statement of main.
+    fn return_type(source: &str) -> CType {
+        let program = type_source(source).unwrap();
+        let TypedNodeKind::Block(nodes) = &program.functions[0].node.kind else {
+            panic!("関数の本体がブロックではありません");
+        };
+        let Some(TypedNodeKind::Return(node)) = nodes.last().map(|node| &node.kind) else {
+            panic!("最後の文が return ではありません");
+        };
+
+        node.ctype.clone()
+    }
+
+    #[test]
+    fn int_plus_int_is_int() {
+        let source = "int main() { int x; int y; return x+y; }";
+        assert_eq!(return_type(source), CType::Int);
+    }
+
+    #[test]
+    fn char_operands_are_promoted() {
+        let source = "int main() { char c; return c+c; }";
+        assert_eq!(return_type(source), CType::Int);
+    }
+
+    #[test]
+    fn address_is_a_pointer() {
+        let source = "int main() { int x; return &x; }";
+        assert_eq!(return_type(source), CType::Ptr(Box::new(CType::Int)));
+    }
+
+    #[test]
+    fn deref_is_the_pointee() {
+        let source = "int main() { char c; char *p=&c; return *p; }";
+        assert_eq!(return_type(source), CType::Char);
+    }
+
+    #[test]
+    fn pointer_plus_int_is_a_pointer() {
+        let source = "int main() { int a[2]; return a+1; }";
+        assert_eq!(return_type(source), CType::Ptr(Box::new(CType::Int)));
+    }
+
+    #[test]
+    fn errors_point_at_the_operator() {
+        let source = "int main() { return *1; }";
+        let error = type_source(source).unwrap_err();
+        assert_eq!(error.message, "ポインタではないものは参照外しできません");
+        assert_eq!(error.span, 20..21);
     }
 }
@@ -0,0 +1,140 @@
+use crate::parser::{Function, Node, NodeKind};
+
+pub struct Dot {
+    count: usize,
+}
+
+impl Dot {
+    pub fn new() -> Self {
+        Self { count: 0 }
+    }
+
+    pub fn dot(&mut self, functions: &[Function]) {
+        println!("digraph AST {{");
+
+        for function in functions {
+            let id = self.gen_label(&format!("Function({})", function.name));
+            let body = self.gen_node(&function.node);
+            gen_edge(id, body, "body");
+        }
+
+        println!("}}");
+    }
+
+    fn gen_label(&mut self, label: &str) -> usize {
+        let id = self.count;
+        self.count += 1;
+        println!("  n{} [label=\"{}\"];", id, label);
+        id
+    }
+
+    fn gen_node(&mut self, node: &Node) -> usize {
+        let (label, children): (String, Vec<(String, &Node)>) = match &node.kind {
+            NodeKind::Num(value) => (format!("Num({})", value), vec![]),
+            NodeKind::Str(value) => (format!("Str({})", value.escape_ascii()), vec![]),
+            NodeKind::Var(var) => (format!("Var({})", var.name), vec![]),
+            NodeKind::GlobalVar(var) => (format!("GlobalVar({})", var.name), vec![]),
+            NodeKind::Func(name) => (format!("Func({})", name), vec![]),
+            NodeKind::ExprStmt(node) => ("ExprStmt".to_string(), vec![("expr".to_string(), node)]),
+            NodeKind::Return(node) => ("Return".to_string(), vec![("expr".to_string(), node)]),
+            NodeKind::Break => ("Break".to_string(), vec![]),
+            NodeKind::Continue => ("Continue".to_string(), vec![]),
+            NodeKind::Block(nodes) => (
+                "Block".to_string(),
+                nodes
+                    .iter()
+                    .enumerate()
+                    .map(|(i, node)| (i.to_string(), node))
+                    .collect(),
+            ),
+            NodeKind::Addr(node) => ("Addr".to_string(), vec![("expr".to_string(), node)]),
+            NodeKind::Deref(node) => ("Deref".to_string(), vec![("expr".to_string(), node)]),
+            NodeKind::Cast(node, ctype) => (
+                format!("Cast({:?})", ctype),
+                vec![("expr".to_string(), node)],
+            ),
+            NodeKind::BitNot(node) => ("BitNot".to_string(), vec![("expr".to_string(), node)]),
+            NodeKind::Sizeof(node) => ("Sizeof".to_string(), vec![("expr".to_string(), node)]),
+            NodeKind::Putchar(node) => ("Putchar".to_string(), vec![("expr".to_string(), node)]),
+            NodeKind::Getchar => ("Getchar".to_string(), vec![]),
+            NodeKind::FuncCall { name, args } => (
+                format!("FuncCall({})", name),
+                args.iter()
+                    .enumerate()
+                    .map(|(i, node)| (format!("arg{}", i), node))
+                    .collect(),
+            ),
+            NodeKind::Generic {
+                control,
+                assocs,
+                default,
+            } => {
+                let mut children = vec![("control".to_string(), control.as_ref())];
+                for (ctype, node) in assocs {
+                    children.push((format!("{:?}", ctype), node));
+                }
+                if let Some(default) = default {
+                    children.push(("default".to_string(), default));
+                }
+                ("Generic".to_string(), children)
+            }
+            NodeKind::If { cond, then, els } => {
+                let mut children = vec![
+                    ("cond".to_string(), cond.as_ref()),
+                    ("then".to_string(), then.as_ref()),
+                ];
+                if let Some(els) = els {
+                    children.push(("els".to_string(), els));
+                }
+                ("If".to_string(), children)
+            }
+            NodeKind::Cond { cond, then, els } => (
+                "Cond".to_string(),
+                vec![
+                    ("cond".to_string(), cond.as_ref()),
+                    ("then".to_string(), then.as_ref()),
+                    ("els".to_string(), els.as_ref()),
+                ],
+            ),
+            NodeKind::For {
+                init,
+                cond,
+                inc,
+                then,
+            } => {
+                let mut children = vec![];
+                for (edge, node) in [("init", init), ("cond", cond), ("inc", inc)] {
+                    if let Some(node) = node {
+                        children.push((edge.to_string(), node.as_ref()));
+                    }
+                }
+                children.push(("then".to_string(), then));
+                ("For".to_string(), children)
+            }
+            NodeKind::DoWhile { then, cond } => (
+                "DoWhile".to_string(),
+                vec![("then".to_string(), then), ("cond".to_string(), cond)],
+            ),
+            NodeKind::BinOp { op, lhs, rhs } => (
+                format!("{:?}", op),
+                vec![("lhs".to_string(), lhs), ("rhs".to_string(), rhs)],
+            ),
+            NodeKind::AssignOp { op, lhs, rhs } => (
+                format!("{:?}Assign", op),
+                vec![("lhs".to_string(), lhs), ("rhs".to_string(), rhs)],
+            ),
+        };
+
+        let id = self.gen_label(&label);
+        for (edge, child) in children {
+            let child = self.gen_node(child);
+            gen_edge(id, child, &edge);
+        }
+
+        id
+    }
+}
+
+fn gen_edge(from: usize, to: usize, label: &str) {
+    println!("  n{} -> n{} [label=\"{}\"];", from, to, label);
+}
@@ -0,0 +1,41 @@
+use std::ops::Range;
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct CompileError {
+    pub message: String,
+    /// Byte range of the offending token in the source
+    pub span: Range<usize>,
+}
+
+impl CompileError {
+    /// Format the error with the line it is on and a caret under the span.
+    pub fn render(&self, source: &str) -> String {
+        let pos = self.span.start;
+        let start = source[..pos].rfind('\n').map_or(0, |i| i + 1);
+        let end = source[pos..].find('\n').map_or(source.len(), |i| pos + i);
+        let (line_no, column) = line_col(source, pos);
+
+        // Keep tabs so the caret lines up however they are rendered
+        let indent: String = source[start..pos]
+            .chars()
+            .map(|c| if c == '\t' { '\t' } else { ' ' })
+            .collect();
+
+        format!(
+            "{}行目 {}列目: {}\n{}\n{}^",
+            line_no,
+            column,
+            self.message,
+            &source[start..end],
+            indent
+        )
+    }
+}
+
+/// 1-based line and column of a byte offset, counting columns in characters.
+pub fn line_col(source: &str, pos: usize) -> (usize, usize) {
+    let start = source[..pos].rfind('\n').map_or(0, |i| i + 1);
+    let line_no = source[..start].matches('\n').count() + 1;
+
+    (line_no, source[start..pos].chars().count() + 1)
+}
@@ -0,0 +1,167 @@
+use crate::{
+    ctype::type_of,
+    error::CompileError,
+    parser::{BinOp, Node, NodeKind},
+};
+
+/// Replace every operator whose operands are both constants with its value.
+///
+/// Dividing by a constant zero is an error unless the division can never
+/// run, e.g. in the rhs of `0 && 1/0`.
+pub fn fold_constants(node: &mut Node) -> Result<(), CompileError> {
+    match &mut node.kind {
+        NodeKind::Num(_)
+        | NodeKind::Str(_)
+        | NodeKind::Var(_)
+        | NodeKind::GlobalVar(_)
+        | NodeKind::Func(_)
+        | NodeKind::Break
+        | NodeKind::Continue
+        | NodeKind::Getchar => {}
+        NodeKind::ExprStmt(node)
+        | NodeKind::Return(node)
+        | NodeKind::Addr(node)
+        | NodeKind::Deref(node)
+        | NodeKind::Cast(node, _)
+        | NodeKind::Putchar(node) => fold_constants(node)?,
+        // The operand is never evaluated, so only its type matters
+        NodeKind::Sizeof(operand) => {
+            let ctype = type_of(*operand.clone())?;
+            if ctype.has_size() {
+                node.kind = NodeKind::Num(ctype.size() as i32);
+            }
+        }
+        NodeKind::BitNot(operand) => {
+            fold_constants(operand)?;
+
+            if let NodeKind::Num(value) = operand.kind {
+                node.kind = NodeKind::Num(!value);
+            }
+        }
+        NodeKind::Block(nodes) | NodeKind::FuncCall { args: nodes, .. } => {
+            for node in nodes {
+                fold_constants(node)?;
+            }
+        }
+        NodeKind::Generic {
+            control,
+            assocs,
+            default,
+        } => {
+            fold_constants(control)?;
+            for (_, node) in assocs {
+                fold_constants(node)?;
+            }
+            if let Some(default) = default {
+                fold_constants(default)?;
+            }
+        }
+        // A branch a constant condition never takes is left as it is
+        NodeKind::If { cond, then, els } => {
+            fold_constants(cond)?;
+            if cond.kind != NodeKind::Num(0) {
+                fold_constants(then)?;
+            }
+            if let Some(els) = els {
+                if !matches!(cond.kind, NodeKind::Num(value) if value != 0) {
+                    fold_constants(els)?;
+                }
+            }
+        }
+        NodeKind::Cond { cond, then, els } => {
+            fold_constants(cond)?;
+            if cond.kind != NodeKind::Num(0) {
+                fold_constants(then)?;
+            }
+            if !matches!(cond.kind, NodeKind::Num(value) if value != 0) {
+                fold_constants(els)?;
+            }
+
+            if let (NodeKind::Num(cond), NodeKind::Num(then), NodeKind::Num(els)) =
+                (&cond.kind, &then.kind, &els.kind)
+            {
+                node.kind = NodeKind::Num(if *cond != 0 { *then } else { *els });
+            }
+        }
+        NodeKind::For {
+            init,
+            cond,
+            inc,
+            then,
+        } => {
+            for node in [init, cond, inc].into_iter().flatten() {
+                fold_constants(node)?;
+            }
+            fold_constants(then)?;
+        }
+        NodeKind::DoWhile { then, cond } => {
+            fold_constants(then)?;
+            fold_constants(cond)?;
+        }
+        NodeKind::AssignOp { op, lhs, rhs } => {
+            fold_constants(lhs)?;
+            fold_constants(rhs)?;
+
+            if matches!(op, BinOp::Div | BinOp::Mod) && rhs.kind == NodeKind::Num(0) {
+                return Err(divide_by_zero(node));
+            }
+        }
+        NodeKind::BinOp { op, lhs, rhs } => {
+            fold_constants(lhs)?;
+
+            // The rhs of a decided && or || is never evaluated, so it is not
+            // folded either
+            if let (BinOp::LogAnd | BinOp::LogOr, NodeKind::Num(value)) = (&op, &lhs.kind) {
+                if (*op == BinOp::LogAnd) == (*value == 0) {
+                    node.kind = NodeKind::Num((*value != 0) as i32);
+                    return Ok(());
+                }
+            }
+            fold_constants(rhs)?;
+
+            if matches!(op, BinOp::Div | BinOp::Mod) && rhs.kind == NodeKind::Num(0) {
+                return Err(divide_by_zero(node));
+            }
+
+            if let (NodeKind::Num(lhs), NodeKind::Num(rhs)) = (&lhs.kind, &rhs.kind) {
+                if let Some(value) = eval(op, *lhs, *rhs) {
+                    node.kind = NodeKind::Num(value);
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn divide_by_zero(node: &Node) -> CompileError {
+    CompileError {
+        message: "ゼロで除算しています".to_string(),
+        span: node.span.clone(),
+    }
+}
+
+fn eval(op: &BinOp, lhs: i32, rhs: i32) -> Option<i32> {
+    let value = match op {
+        BinOp::Add => lhs.wrapping_add(rhs),
+        BinOp::Sub => lhs.wrapping_sub(rhs),
+        BinOp::Mul => lhs.wrapping_mul(rhs),
+        BinOp::Div => lhs.wrapping_div(rhs),
+        BinOp::Mod => lhs.wrapping_rem(rhs),
+        BinOp::Eq => (lhs == rhs) as i32,
+        BinOp::Ne => (lhs != rhs) as i32,
+        BinOp::Lt => (lhs < rhs) as i32,
+        BinOp::Le => (lhs <= rhs) as i32,
+        BinOp::BitAnd => lhs & rhs,
+        BinOp::BitOr => lhs | rhs,
+        BinOp::BitXor => lhs ^ rhs,
+        BinOp::Shl => lhs.wrapping_shl(rhs as u32),
+        BinOp::Shr => lhs.wrapping_shr(rhs as u32),
+        BinOp::LogAnd => (lhs != 0 && rhs != 0) as i32,
+        BinOp::LogOr => (lhs != 0 || rhs != 0) as i32,
+        BinOp::Comma => rhs,
+        BinOp::Assign => return None,
+    };
+
+    Some(value)
+}
@@ -1,8 +1,14 @@
+use std::ops::Range;
+
+use crate::error::CompileError;
+
 #[derive(Debug, PartialEq, Eq)]
 pub enum TokenKind {
     Reserved,
     Ident,
     Num(i32),
+    /// The bytes of a string literal with its escapes resolved
+    Str(Vec<u8>),
     Eof,
 }
 
@@ -10,6 +16,24 @@ pub enum TokenKind {
 pub struct Token<'src> {
     pub kind: TokenKind,
     pub raw_str: &'src str,
+    /// Byte offset of the token in the source
+    pub pos: usize,
+}
+
+impl Token<'_> {
+    pub fn span(&self) -> Range<usize> {
+        self.pos..self.pos + self.raw_str.len()
+    }
+
+    pub fn describe(&self) -> String {
+        match self.kind {
+            TokenKind::Reserved => format!("予約語 '{}'", self.raw_str),
+            TokenKind::Ident => format!("識別子 '{}'", self.raw_str),
+            TokenKind::Num(_) => format!("数 '{}'", self.raw_str),
+            TokenKind::Str(_) => format!("文字列 {}", self.raw_str),
+            TokenKind::Eof => "入力の終わり".to_string(),
+        }
+    }
 }
 
 pub struct Lexer<'src> {
@@ -17,6 +41,53 @@ pub struct Lexer<'src> {
     cursor: usize,
 }
 
+const KEYWORDS: [&str; 44] = [
+    "auto",
+    "break",
+    "case",
+    "char",
+    "const",
+    "continue",
+    "default",
+    "do",
+    "double",
+    "else",
+    "enum",
+    "extern",
+    "float",
+    "for",
+    "goto",
+    "if",
+    "inline",
+    "int",
+    "long",
+    "register",
+    "restrict",
+    "return",
+    "short",
+    "signed",
+    "sizeof",
+    "static",
+    "struct",
+    "switch",
+    "typedef",
+    "union",
+    "unsigned",
+    "void",
+    "volatile",
+    "while",
+    "_Alignas",
+    "_Alignof",
+    "_Atomic",
+    "_Bool",
+    "_Complex",
+    "_Generic",
+    "_Imaginary",
+    "_Noreturn",
+    "_Static_assert",
+    "_Thread_local",
+];
+
 fn is_ident_first(c: char) -> bool {
     c.is_ascii_alphabetic() || c == '_'
 }
@@ -30,38 +101,148 @@ impl<'src> Lexer<'src> {
         Self { source, cursor: 0 }
     }
 
-    pub fn lex(&mut self) -> Vec<Token<'src>> {
+    fn error(&self, message: &str, span: Range<usize>) -> CompileError {
+        CompileError {
+            message: message.to_string(),
+            span,
+        }
+    }
+
+    pub fn lex(&mut self) -> Result<Vec<Token<'src>>, CompileError> {
         let mut tokens = vec![];
 
         'outer: while self.cursor < self.source.len() {
             let c = self.source[self.cursor..].chars().next().unwrap();
 
             if c.is_whitespace() {
-                self.cursor += 1;
+                self.cursor += c.len_utf8();
                 continue;
             }
 
-            for keyword in ["return", "if", "else", "for", "while"] {
-                if let Some(rest) = self.source[self.cursor..].strip_prefix(keyword) {
-                    if rest.is_empty() || !is_ident_follow(rest.chars().next().unwrap()) {
-                        tokens.push(Token {
-                            kind: TokenKind::Reserved,
-                            raw_str: &self.source[self.cursor..self.cursor + keyword.len()],
-                        });
-                        self.cursor += keyword.len();
-                        continue 'outer;
+            let rest = &self.source[self.cursor..];
+            if rest.starts_with("//") {
+                self.cursor += rest.find('\n').unwrap_or(rest.len());
+                continue;
+            }
+            if let Some(body) = rest.strip_prefix("/*") {
+                let Some(end) = body.find("*/") else {
+                    let start = self.cursor;
+                    return Err(
+                        self.error("ブロックコメントが閉じられていません", start..start + 2)
+                    );
+                };
+                self.cursor += 2 + end + 2;
+                continue;
+            }
+
+            if c == '"' {
+                let start = self.cursor;
+                let mut value = vec![];
+                let mut chars = rest.char_indices().skip(1).peekable();
+                loop {
+                    let Some((i, ch)) = chars.next().filter(|&(_, ch)| ch != '\n') else {
+                        return Err(
+                            self.error("文字列リテラルが閉じられていません", start..start + 1)
+                        );
+                    };
+                    match ch {
+                        '"' => {
+                            self.cursor += i + 1;
+                            break;
+                        }
+                        '\\' => {
+                            let Some((_, escaped)) = chars.next() else {
+                                return Err(self.error(
+                                    "文字列リテラルが閉じられていません",
+                                    start..start + 1,
+                                ));
+                            };
+                            value.push(match escaped {
+                                'n' => b'\n',
+                                't' => b'\t',
+                                'r' => b'\r',
+                                'a' => 0x07,
+                                'b' => 0x08,
+                                'f' => 0x0c,
+                                'v' => 0x0b,
+                                '\\' | '"' | '\'' | '?' => escaped as u8,
+                                // Up to three octal digits, as in \0 and \177
+                                '0'..='7' => {
+                                    let mut code = escaped.to_digit(8).unwrap();
+                                    for _ in 0..2 {
+                                        let Some(digit) =
+                                            chars.peek().and_then(|&(_, ch)| ch.to_digit(8))
+                                        else {
+                                            break;
+                                        };
+                                        code = code * 8 + digit;
+                                        chars.next();
+                                    }
+                                    let Ok(byte) = u8::try_from(code) else {
+                                        let end = chars.peek().map_or(rest.len(), |&(j, _)| j);
+                                        return Err(self.error(
+                                            "八進エスケープが範囲外です",
+                                            start + i..start + end,
+                                        ));
+                                    };
+                                    byte
+                                }
+                                // Any number of hex digits, as long as they fit a byte
+                                'x' => {
+                                    let mut code = None;
+                                    while let Some(digit) =
+                                        chars.peek().and_then(|&(_, ch)| ch.to_digit(16))
+                                    {
+                                        let value = code.unwrap_or(0u32);
+                                        code = Some(value.saturating_mul(16).saturating_add(digit));
+                                        chars.next();
+                                    }
+                                    let end = chars.peek().map_or(rest.len(), |&(j, _)| j);
+                                    let Some(code) = code else {
+                                        return Err(self.error(
+                                            "十六進エスケープに数字がありません",
+                                            start + i..start + end,
+                                        ));
+                                    };
+                                    let Ok(byte) = u8::try_from(code) else {
+                                        return Err(self.error(
+                                            "十六進エスケープが範囲外です",
+                                            start + i..start + end,
+                                        ));
+                                    };
+                                    byte
+                                }
+                                _ => {
+                                    let end = chars.peek().map_or(rest.len(), |&(j, _)| j);
+                                    return Err(self.error(
+                                        "不明なエスケープシーケンスです",
+                                        start + i..start + end,
+                                    ));
+                                }
+                            });
+                        }
+                        ch => value.extend_from_slice(ch.encode_utf8(&mut [0; 4]).as_bytes()),
                     }
                 }
+
+                tokens.push(Token {
+                    kind: TokenKind::Str(value),
+                    raw_str: &self.source[start..self.cursor],
+                    pos: start,
+                });
+                continue;
             }
 
             for punct in [
-                "==", "!=", "<=", ">=", "+", "-", "*", "/", "{", "}", "(", ")", "<", ">", ";", "=",
-                "&",
+                "<<=", ">>=", "==", "!=", "<=", ">=", "&&", "||", "++", "--", "+=", "-=", "*=",
+                "/=", "%=", "&=", "|=", "^=", "<<", ">>", "+", "-", "*", "/", "{", "}", "(", ")",
+                "<", ">", ";", "=", "&", ",", ":", "[", "]", "%", "?", "|", "^", "~",
             ] {
                 if self.source[self.cursor..].starts_with(punct) {
                     tokens.push(Token {
                         kind: TokenKind::Reserved,
                         raw_str: &self.source[self.cursor..self.cursor + punct.len()],
+                        pos: self.cursor,
                     });
                     self.cursor += punct.len();
                     continue 'outer;
@@ -79,44 +260,90 @@ impl<'src> Lexer<'src> {
                     self.cursor += 1;
                 }
 
+                let raw_str = &self.source[start..self.cursor];
+                let kind = if KEYWORDS.contains(&raw_str) {
+                    TokenKind::Reserved
+                } else {
+                    TokenKind::Ident
+                };
                 tokens.push(Token {
-                    kind: TokenKind::Ident,
-                    raw_str: &self.source[start..self.cursor],
+                    kind,
+                    raw_str,
+                    pos: start,
                 });
                 continue;
             }
 
             if c.is_ascii_digit() {
                 let start = self.cursor;
-                while self.cursor < self.source.len()
-                    && self.source[self.cursor..]
-                        .chars()
-                        .next()
-                        .unwrap()
-                        .is_ascii_digit()
-                {
+                let bytes = self.source.as_bytes();
+                let next = bytes.get(start + 1).map(u8::to_ascii_lowercase);
+                let after = bytes.get(start + 2);
+                let radix = match (c, next) {
+                    ('0', Some(b'x')) => 16,
+                    ('0', Some(b'b')) => 2,
+                    ('0', Some(b'0'..=b'9')) => 8,
+                    ('0', Some(b'\'')) if after.is_some_and(u8::is_ascii_digit) => 8,
+                    _ => 10,
+                };
+                // The leading 0 of an octal literal is one of its digits, so a
+                // separator may follow it
+                self.cursor += match radix {
+                    16 | 2 => 2,
+                    _ => 0,
+                };
+
+                let digits_start = self.cursor;
+                while self.cursor < self.source.len() {
+                    if bytes[self.cursor] == b'\'' {
+                        // A digit separator must sit between two digits
+                        if self.cursor == digits_start
+                            || !bytes
+                                .get(self.cursor + 1)
+                                .is_some_and(|&b| (b as char).is_digit(radix))
+                        {
+                            return Err(self.error(
+                                "数字区切りの位置が正しくありません",
+                                self.cursor..self.cursor + 1,
+                            ));
+                        }
+                    } else if !bytes[self.cursor].is_ascii_alphanumeric() {
+                        break;
+                    }
                     self.cursor += 1;
                 }
 
+                let raw_str = &self.source[start..self.cursor];
+                let digits = self.source[digits_start..self.cursor].replace('\'', "");
+                if digits.is_empty() {
+                    return Err(self.error("数字がありません", start..self.cursor));
+                }
+                if !digits.chars().all(|c| c.is_digit(radix)) {
+                    return Err(self.error("数値リテラルが正しくありません", start..self.cursor));
+                }
+                let Ok(value) = i32::from_str_radix(&digits, radix) else {
+                    return Err(self.error("数値が大きすぎます", start..self.cursor));
+                };
                 tokens.push(Token {
-                    kind: TokenKind::Num(
-                        self.source[start..self.cursor]
-                            .parse::<i32>()
-                            .expect("数字へのパースに失敗"),
-                    ),
-                    raw_str: &self.source[start..self.cursor],
+                    kind: TokenKind::Num(value),
+                    raw_str,
+                    pos: start,
                 });
                 continue;
             }
 
-            panic!("トークナイズできません: {}", &self.source[self.cursor..]);
+            return Err(self.error(
+                "トークナイズできません",
+                self.cursor..self.cursor + c.len_utf8(),
+            ));
         }
 
         tokens.push(Token {
             kind: TokenKind::Eof,
             raw_str: "",
+            pos: self.source.len(),
         });
 
-        tokens
+        Ok(tokens)
     }
 }
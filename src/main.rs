@@ -1,29 +1,149 @@
 use core::panic;
-use std::env::args;
+use std::{
+    env::args,
+    io::{self, BufWriter, Write},
+    process,
+};
 
-use codegen::Codegen;
-use ctype::type_function;
+use codegen::{Codegen, CodegenOptions};
+use ctype::{type_program, TypedProgram};
+use dot::Dot;
+use error::CompileError;
+use fold::fold_constants;
 use lexer::Lexer;
-use parser::Parser;
+use parser::{Parser, ParserOptions};
 
 mod codegen;
 mod ctype;
+mod dot;
+mod error;
+mod fold;
 mod lexer;
 mod parser;
 
+#[derive(PartialEq, Eq)]
+enum Phase {
+    Lex,
+    Parse,
+    Type,
+    Codegen,
+}
+
 fn main() {
-    let args = args().collect::<Vec<_>>();
-    if args.len() != 2 {
+    let mut stop_after = Phase::Codegen;
+    let mut expr_mode = false;
+    let mut ast_dot = false;
+    let mut parser_options = ParserOptions::default();
+    let mut options = CodegenOptions::default();
+    let mut input = None;
+
+    let mut args = args().skip(1);
+    while let Some(arg) = args.next() {
+        if arg == "--stop-after" {
+            stop_after = match args.next().as_deref() {
+                Some("lex") => Phase::Lex,
+                Some("parse") => Phase::Parse,
+                Some("type") => Phase::Type,
+                Some("codegen") => Phase::Codegen,
+                _ => panic!(
+                    "--stop-after には lex, parse, type, codegen のいずれかを指定してください"
+                ),
+            };
+            continue;
+        }
+
+        if arg == "-fno-omit-frame-pointer" {
+            options.frame_pointer = true;
+            continue;
+        }
+
+        if arg == "-Wshadow" {
+            parser_options.warn_shadow = true;
+            continue;
+        }
+
+        if arg == "-Wno-shadow" {
+            parser_options.warn_shadow = false;
+            continue;
+        }
+
+        if arg == "-mno-relax" {
+            options.no_relax = true;
+            continue;
+        }
+
+        if arg == "-ftrivial-auto-var-init=zero" {
+            options.zero_init_locals = true;
+            continue;
+        }
+
+        if arg == "--ast-dot" {
+            ast_dot = true;
+            continue;
+        }
+
+        if arg == "--expr" {
+            expr_mode = true;
+            continue;
+        }
+
+        if input.is_some() {
+            panic!("引数の個数が正しくありません");
+        }
+        input = Some(arg);
+    }
+    let Some(input) = input else {
         panic!("引数の個数が正しくありません");
+    };
+    // A bare expression becomes the body of main
+    let input = if expr_mode {
+        format!("int main() {{ return {}; }}", input)
+    } else {
+        input
+    };
+
+    let mut lexer = Lexer::new(&input);
+    let tokens = lexer.lex().unwrap_or_else(|error| fail(&input, error));
+    if stop_after == Phase::Lex {
+        for token in &tokens {
+            println!("{:?}", token);
+        }
+        return;
     }
 
-    let mut lexer = Lexer::new(&args[1]);
-    let tokens = lexer.lex();
-    let mut parser = Parser::new(&args[1], tokens);
+    let mut parser = Parser::new(&input, tokens, parser_options);
+    let mut program = parser.parse().unwrap_or_else(|error| fail(&input, error));
+    if ast_dot {
+        Dot::new().dot(&program.functions);
+        return;
+    }
+    if stop_after == Phase::Parse {
+        println!("{:#?}", program);
+        return;
+    }
 
-    let function = parser.parse();
-    let typed_function = type_function(function);
+    for function in &mut program.functions {
+        fold_constants(&mut function.node).unwrap_or_else(|error| fail(&input, error));
+    }
+    let typed_program = type_program(program).unwrap_or_else(|error| fail(&input, error));
+    if stop_after == Phase::Type {
+        println!("{:#?}", typed_program);
+        return;
+    }
+
+    if let Err(e) = emit(io::stdout().lock(), options, typed_program) {
+        panic!("アセンブリの書き出しに失敗しました: {}", e);
+    }
+}
+
+/// Report a compile error in `source` and exit.
+fn fail(source: &str, error: CompileError) -> ! {
+    eprintln!("{}", error.render(source));
+    process::exit(1);
+}
 
-    let mut codegen = Codegen::new();
-    codegen.codegen(typed_function);
+fn emit(out: impl Write, options: CodegenOptions, program: TypedProgram) -> io::Result<()> {
+    let mut out = BufWriter::new(out);
+    Codegen::new(&mut out, options).codegen(program)?;
+    out.flush()
 }
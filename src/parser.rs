@@ -1,9 +1,53 @@
-use crate::lexer::{Token, TokenKind};
+use std::ops::Range;
+
+use crate::{
+    ctype::{type_of, CType},
+    error::{line_col, CompileError},
+    fold::fold_constants,
+    lexer::{Token, TokenKind},
+};
+
+#[derive(Debug)]
+pub struct Program<'src> {
+    pub globals: Vec<GlobalVar<'src>>,
+    pub functions: Vec<Function<'src>>,
+}
 
 #[derive(Debug)]
 pub struct Function<'src> {
+    pub name: &'src str,
+    pub params: Vec<&'src str>,
     pub node: Node<'src>,
-    pub locals: Vec<&'src str>,
+    pub locals: Vec<LocalVar<'src>>,
+    pub is_leaf: bool,
+}
+
+impl Function<'_> {
+    /// Bytes needed for all locals, rounded up to keep sp 16-byte aligned.
+    pub fn frame_size(&self) -> usize {
+        let size = self
+            .locals
+            .iter()
+            .map(|var| -var.offset as usize)
+            .max()
+            .unwrap_or(0);
+
+        size.div_ceil(16) * 16
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct LocalVar<'src> {
+    pub name: &'src str,
+    /// Offset from fp
+    pub offset: i32,
+    pub ctype: CType,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct GlobalVar<'src> {
+    pub name: &'src str,
+    pub ctype: CType,
 }
 
 #[derive(Debug, PartialEq, Eq, Clone)]
@@ -12,65 +56,147 @@ pub enum BinOp {
     Sub,
     Mul,
     Div,
+    Mod,
     Eq,
     Ne,
     Lt,
     Le,
+    BitAnd,
+    BitOr,
+    BitXor,
+    Shl,
+    Shr,
+    LogAnd,
+    LogOr,
     Assign,
+    Comma,
 }
 
 #[derive(Debug, PartialEq, Eq, Clone)]
 pub enum NodeKind<'src> {
     Num(i32),
+    Str(Vec<u8>),
     ExprStmt(Box<Node<'src>>),
-    Var(&'src str),
+    Var(LocalVar<'src>),
+    GlobalVar(GlobalVar<'src>),
+    Func(&'src str),
     Return(Box<Node<'src>>),
+    Break,
+    Continue,
     Block(Vec<Node<'src>>),
     Addr(Box<Node<'src>>),
     Deref(Box<Node<'src>>),
+    BitNot(Box<Node<'src>>),
+    Sizeof(Box<Node<'src>>),
+    /// Convert the value to the given type
+    Cast(Box<Node<'src>>, CType),
+    Putchar(Box<Node<'src>>),
+    Getchar,
+    FuncCall {
+        name: &'src str,
+        args: Vec<Node<'src>>,
+    },
+    Generic {
+        control: Box<Node<'src>>,
+        assocs: Vec<(CType, Node<'src>)>,
+        default: Option<Box<Node<'src>>>,
+    },
     If {
         cond: Box<Node<'src>>,
         then: Box<Node<'src>>,
         els: Option<Box<Node<'src>>>,
     },
+    Cond {
+        cond: Box<Node<'src>>,
+        then: Box<Node<'src>>,
+        els: Box<Node<'src>>,
+    },
     For {
         init: Option<Box<Node<'src>>>,
         cond: Option<Box<Node<'src>>>,
         inc: Option<Box<Node<'src>>>,
         then: Box<Node<'src>>,
     },
+    /// A loop that tests `cond` after each run of `then`
+    DoWhile {
+        then: Box<Node<'src>>,
+        cond: Box<Node<'src>>,
+    },
     BinOp {
         op: BinOp,
         lhs: Box<Node<'src>>,
         rhs: Box<Node<'src>>,
     },
+    /// `lhs op= rhs`, evaluating the address of `lhs` once
+    AssignOp {
+        op: BinOp,
+        lhs: Box<Node<'src>>,
+        rhs: Box<Node<'src>>,
+    },
 }
 
 #[derive(Debug, PartialEq, Eq, Clone)]
 pub struct Node<'src> {
     pub kind: NodeKind<'src>,
+    /// Byte range of the token the node is reported at
+    pub span: Range<usize>,
 }
 
 impl<'src> Node<'src> {
-    pub fn new(kind: NodeKind<'src>) -> Self {
-        Self { kind }
+    pub fn new(kind: NodeKind<'src>, span: Range<usize>) -> Self {
+        Self { kind, span }
+    }
+
+    fn binop(op: BinOp, lhs: Node<'src>, rhs: Node<'src>, span: Range<usize>) -> Self {
+        Self::new(
+            NodeKind::BinOp {
+                op,
+                lhs: Box::new(lhs),
+                rhs: Box::new(rhs),
+            },
+            span,
+        )
     }
 }
 
+#[derive(Default)]
+pub struct ParserOptions {
+    /// Warn when a declaration hides a parameter or an outer variable.
+    pub warn_shadow: bool,
+}
+
 pub struct Parser<'src> {
     source: &'src str,
+    options: ParserOptions,
     tokens: Vec<Token<'src>>,
     cursor: usize,
-    locals: Vec<&'src str>,
+    locals: Vec<LocalVar<'src>>,
+    /// Variables visible at the cursor with the byte offset of their
+    /// declaration, innermost block last
+    scopes: Vec<Vec<(LocalVar<'src>, usize)>>,
+    globals: Vec<GlobalVar<'src>>,
+    /// Functions defined so far, including the current one
+    functions: Vec<&'src str>,
+    /// Number of loops enclosing the cursor
+    loop_depth: usize,
+    stack_size: usize,
+    is_leaf: bool,
 }
 
 impl<'src> Parser<'src> {
-    pub fn new(source: &'src str, tokens: Vec<Token<'src>>) -> Self {
+    pub fn new(source: &'src str, tokens: Vec<Token<'src>>, options: ParserOptions) -> Self {
         Self {
             source,
+            options,
             tokens,
             cursor: 0,
             locals: vec![],
+            scopes: vec![],
+            globals: vec![],
+            functions: vec![],
+            loop_depth: 0,
+            stack_size: 0,
+            is_leaf: true,
         }
     }
 
@@ -83,295 +209,982 @@ impl<'src> Parser<'src> {
         true
     }
 
-    pub fn expect(&mut self, op: &str) {
+    fn consume_builtin(&mut self, name: &str) -> bool {
+        let token = &self.tokens[self.cursor];
+        if token.kind != TokenKind::Ident || token.raw_str != name {
+            return false;
+        }
+        self.cursor += 1;
+        true
+    }
+
+    pub fn expect(&mut self, op: &str) -> Result<(), CompileError> {
         let token = &self.tokens[self.cursor];
         if token.kind != TokenKind::Reserved || token.raw_str != op {
-            self.error_at(&format!("'{}' ではありません", op));
+            return Err(self.error_at(&format!(
+                "'{}' が必要ですが、{} が見つかりました",
+                op,
+                token.describe()
+            )));
         }
         self.cursor += 1;
+        Ok(())
     }
 
-    pub fn expect_number(&mut self) -> i32 {
+    pub fn expect_number(&mut self) -> Result<i32, CompileError> {
         let token = &self.tokens[self.cursor];
         if let TokenKind::Num(value) = token.kind {
             self.cursor += 1;
-            value
+            Ok(value)
         } else {
-            self.error_at("数ではありません");
+            Err(self.error_at("数ではありません"))
         }
     }
 
+    pub fn expect_ident(&mut self) -> Result<&'src str, CompileError> {
+        let token = &self.tokens[self.cursor];
+        if token.kind != TokenKind::Ident {
+            return Err(self.error_at("識別子ではありません"));
+        }
+        self.cursor += 1;
+        Ok(token.raw_str)
+    }
+
     pub fn at_eof(&self) -> bool {
         self.tokens[self.cursor].kind == TokenKind::Eof
     }
 
-    pub fn error_at(&self, message: &str) -> ! {
-        panic!(
-            "{}\n{:>width$}\n{}",
-            self.source,
-            "^",
-            message,
-            width = self.cursor + 1
-        );
+    pub fn error_at(&self, message: &str) -> CompileError {
+        CompileError {
+            message: message.to_string(),
+            span: self.tokens[self.cursor].span(),
+        }
     }
 
-    pub fn parse(&mut self) -> Function {
-        self.expect("{");
+    /// Byte range of the token just consumed.
+    fn prev_span(&self) -> Range<usize> {
+        self.tokens[self.cursor - 1].span()
+    }
 
-        Function {
-            node: self.compound_stmt(),
-            locals: self.locals.clone(),
+    pub fn parse(&mut self) -> Result<Program<'src>, CompileError> {
+        let mut functions = vec![];
+        while !self.at_eof() {
+            if self.is_function()? {
+                functions.push(self.function()?);
+            } else {
+                self.global_variable()?;
+            }
+        }
+
+        Ok(Program {
+            globals: self.globals.clone(),
+            functions,
+        })
+    }
+
+    /// Look past the declarator to see whether a function or globals follow.
+    fn is_function(&mut self) -> Result<bool, CompileError> {
+        let start = self.cursor;
+        self.consume("inline");
+        let base = self.declspec()?;
+        self.declarator(base)?;
+        let is_function = self.tokens[self.cursor].raw_str == "(";
+        self.cursor = start;
+
+        Ok(is_function)
+    }
+
+    fn global_variable(&mut self) -> Result<(), CompileError> {
+        if self.tokens[self.cursor].raw_str == "inline" {
+            return Err(self.error_at("inline は関数にしか指定できません"));
+        }
+        let base = self.declspec()?;
+        loop {
+            let (name, pos, ctype) = self.declarator(base.clone())?;
+            if !ctype.has_size() {
+                return Err(unsized_variable(name, pos));
+            }
+            if self.globals.iter().any(|var| var.name == name) {
+                return Err(self.error_at("変数が二重に宣言されています"));
+            }
+            self.globals.push(GlobalVar { name, ctype });
+
+            if self.consume(";") {
+                return Ok(());
+            }
+            self.expect(",")?;
+        }
+    }
+
+    fn function(&mut self) -> Result<Function<'src>, CompileError> {
+        self.locals.clear();
+        self.scopes = vec![vec![]];
+        self.stack_size = 0;
+        self.is_leaf = true;
+
+        // There is no inliner, so inline is accepted and otherwise ignored
+        self.consume("inline");
+        let base = self.declspec()?;
+        let (name, _, _) = self.declarator(base)?;
+        self.functions.push(name);
+
+        self.expect("(")?;
+        let mut params = vec![];
+        if !self.consume(")") {
+            loop {
+                if params.len() == 8 {
+                    return Err(self.error_at("引数は 8 個までです"));
+                }
+                let base = self.declspec()?;
+                let (param, pos, ctype) = self.declarator(base)?;
+                // An array parameter is passed as a pointer to its first element
+                let ctype = match ctype {
+                    CType::Array(base, _) => CType::Ptr(base),
+                    ctype => ctype,
+                };
+                params.push(param);
+                self.declare(param, pos, ctype)?;
+
+                if self.consume(")") {
+                    break;
+                }
+                self.expect(",")?;
+            }
         }
+
+        // The parameters and the outermost block of the body share a scope,
+        // so redeclaring a parameter there is an error
+        self.expect("{")?;
+        let node = self.compound_stmt()?;
+
+        Ok(Function {
+            name,
+            params,
+            node,
+            locals: self.locals.clone(),
+            is_leaf: self.is_leaf,
+        })
     }
 
-    fn stmt(&mut self) -> Node<'src> {
+    fn stmt(&mut self) -> Result<Node<'src>, CompileError> {
         if self.consume("return") {
-            let node = Node::new(NodeKind::Return(Box::new(self.expr())));
-            self.expect(";");
+            let span = self.prev_span();
+            let node = Node::new(NodeKind::Return(Box::new(self.expr()?)), span);
+            self.expect(";")?;
 
-            return node;
+            return Ok(node);
+        }
+
+        if self.consume("break") {
+            let span = self.prev_span();
+            if self.loop_depth == 0 {
+                self.cursor -= 1;
+                return Err(self.error_at("break はループの中でしか使えません"));
+            }
+            self.expect(";")?;
+            return Ok(Node::new(NodeKind::Break, span));
+        }
+
+        if self.consume("continue") {
+            let span = self.prev_span();
+            if self.loop_depth == 0 {
+                self.cursor -= 1;
+                return Err(self.error_at("continue はループの中でしか使えません"));
+            }
+            self.expect(";")?;
+            return Ok(Node::new(NodeKind::Continue, span));
         }
 
         if self.consume("if") {
-            self.expect("(");
-            let cond = self.expr();
-            self.expect(")");
-            let then = self.stmt();
+            let span = self.prev_span();
+            self.expect("(")?;
+            let cond = self.expr()?;
+            self.expect(")")?;
+            let then = self.stmt()?;
             let mut els = None;
             if self.consume("else") {
-                els = Some(self.stmt());
+                els = Some(self.stmt()?);
             }
 
-            return Node::new(NodeKind::If {
-                cond: Box::new(cond),
-                then: Box::new(then),
-                els: els.map(Box::new),
-            });
+            return Ok(Node::new(
+                NodeKind::If {
+                    cond: Box::new(cond),
+                    then: Box::new(then),
+                    els: els.map(Box::new),
+                },
+                span,
+            ));
         }
 
         if self.consume("for") {
-            self.expect("(");
-            let init = Some(self.expr_stmt());
+            let span = self.prev_span();
+            self.expect("(")?;
+            self.enter_scope();
+            let init = if self.is_typename() {
+                Some(self.declaration()?)
+            } else {
+                Some(self.expr_stmt()?)
+            };
 
             let mut cond = None;
             if !self.consume(";") {
-                cond = Some(self.expr());
-                self.expect(";");
+                cond = Some(self.expr()?);
+                self.expect(";")?;
             }
 
             let mut inc = None;
             if !self.consume(")") {
-                inc = Some(self.expr());
-                self.expect(")");
+                inc = Some(self.expr()?);
+                self.expect(")")?;
             }
 
-            let then = self.stmt();
+            let then = self.loop_body()?;
+            self.leave_scope();
 
-            return Node::new(NodeKind::For {
-                init: init.map(Box::new),
-                cond: cond.map(Box::new),
-                inc: inc.map(Box::new),
-                then: Box::new(then),
-            });
+            return Ok(Node::new(
+                NodeKind::For {
+                    init: init.map(Box::new),
+                    cond: cond.map(Box::new),
+                    inc: inc.map(Box::new),
+                    then: Box::new(then),
+                },
+                span,
+            ));
         }
 
         if self.consume("while") {
-            self.expect("(");
-            let cond = Some(self.expr());
-            self.expect(")");
-            let then = self.stmt();
-
-            return Node::new(NodeKind::For {
-                init: None,
-                cond: cond.map(Box::new),
-                inc: None,
-                then: Box::new(then),
-            });
+            let span = self.prev_span();
+            self.expect("(")?;
+            let cond = Some(self.expr()?);
+            self.expect(")")?;
+            let then = self.loop_body()?;
+
+            return Ok(Node::new(
+                NodeKind::For {
+                    init: None,
+                    cond: cond.map(Box::new),
+                    inc: None,
+                    then: Box::new(then),
+                },
+                span,
+            ));
+        }
+
+        if self.consume("do") {
+            let span = self.prev_span();
+            let then = self.loop_body()?;
+            self.expect("while")?;
+            self.expect("(")?;
+            let cond = self.expr()?;
+            self.expect(")")?;
+            self.expect(";")?;
+
+            return Ok(Node::new(
+                NodeKind::DoWhile {
+                    then: Box::new(then),
+                    cond: Box::new(cond),
+                },
+                span,
+            ));
         }
 
         if self.consume("{") {
-            return self.compound_stmt();
+            self.enter_scope();
+            let node = self.compound_stmt()?;
+            self.leave_scope();
+            return Ok(node);
         }
 
         self.expr_stmt()
     }
 
-    fn compound_stmt(&mut self) -> Node<'src> {
+    fn loop_body(&mut self) -> Result<Node<'src>, CompileError> {
+        self.loop_depth += 1;
+        let node = self.stmt()?;
+        self.loop_depth -= 1;
+        Ok(node)
+    }
+
+    fn compound_stmt(&mut self) -> Result<Node<'src>, CompileError> {
+        let span = self.prev_span();
         let mut nodes = vec![];
         while !self.consume("}") {
-            nodes.push(self.stmt());
+            if self.is_typename() {
+                nodes.push(self.declaration()?);
+            } else {
+                nodes.push(self.stmt()?);
+            }
+        }
+
+        Ok(Node::new(NodeKind::Block(nodes), span))
+    }
+
+    fn declaration(&mut self) -> Result<Node<'src>, CompileError> {
+        if self.consume("inline") {
+            self.cursor -= 1;
+            return Err(self.error_at("inline は関数にしか指定できません"));
+        }
+        let span = self.tokens[self.cursor].span();
+        let base = self.declspec()?;
+
+        let mut nodes = vec![];
+        let mut first = true;
+        while !self.consume(";") {
+            if !first {
+                self.expect(",")?;
+            }
+            first = false;
+
+            let (name, pos, ctype) = self.declarator(base.clone())?;
+            if self.is_function_definition() {
+                self.cursor -= 1;
+                return Err(self.error_at("関数の中で関数を定義することはできません"));
+            }
+            let var = self.declare(name, pos, ctype)?;
+            if self.consume("=") {
+                let span = self.prev_span();
+                let var = Node::new(NodeKind::Var(var), pos..pos + name.len());
+                let node = Node::binop(BinOp::Assign, var, self.assign()?, span.clone());
+                nodes.push(Node::new(NodeKind::ExprStmt(Box::new(node)), span));
+            }
         }
 
-        Node::new(NodeKind::Block(nodes))
+        Ok(Node::new(NodeKind::Block(nodes), span))
     }
 
-    fn expr_stmt(&mut self) -> Node<'src> {
+    /// Whether the tokens at the cursor are a parameter list followed by a body.
+    fn is_function_definition(&self) -> bool {
+        if self.tokens[self.cursor].raw_str != "(" {
+            return false;
+        }
+
+        let mut depth = 0;
+        for (i, token) in self.tokens[self.cursor..].iter().enumerate() {
+            match token.raw_str {
+                "(" => depth += 1,
+                ")" => depth -= 1,
+                _ if token.kind == TokenKind::Eof => return false,
+                _ => {}
+            }
+            if depth == 0 {
+                return self.tokens[self.cursor + i + 1].raw_str == "{";
+            }
+        }
+
+        false
+    }
+
+    fn enter_scope(&mut self) {
+        self.scopes.push(vec![]);
+    }
+
+    fn leave_scope(&mut self) {
+        self.scopes.pop();
+    }
+
+    fn declare(
+        &mut self,
+        name: &'src str,
+        pos: usize,
+        ctype: CType,
+    ) -> Result<LocalVar<'src>, CompileError> {
+        if !ctype.has_size() {
+            return Err(unsized_variable(name, pos));
+        }
+        let scope = self.scopes.last().unwrap();
+        if scope.iter().any(|(var, _)| var.name == name) {
+            return Err(self.error_at("変数が二重に宣言されています"));
+        }
+        if self.options.warn_shadow {
+            if let Some((_, shadowed)) = self.find_decl(name) {
+                self.warn_shadow(name, pos, *shadowed);
+            }
+        }
+
+        self.stack_size = (self.stack_size + ctype.size()).next_multiple_of(ctype.align());
+        let var = LocalVar {
+            name,
+            offset: -(self.stack_size as i32),
+            ctype,
+        };
+        self.locals.push(var.clone());
+        self.scopes.last_mut().unwrap().push((var.clone(), pos));
+        Ok(var)
+    }
+
+    fn warn_shadow(&self, name: &str, pos: usize, shadowed: usize) {
+        let (line_no, column) = line_col(self.source, shadowed);
+        let warning = CompileError {
+            message: format!(
+                "警告: '{}' の宣言が {}行目 {}列目 の宣言を隠しています",
+                name, line_no, column
+            ),
+            span: pos..pos + name.len(),
+        };
+        eprintln!("{}", warning.render(self.source));
+    }
+
+    fn find_decl(&self, name: &str) -> Option<&(LocalVar<'src>, usize)> {
+        self.scopes
+            .iter()
+            .rev()
+            .find_map(|scope| scope.iter().find(|(var, _)| var.name == name))
+    }
+
+    fn find_var(&self, name: &str) -> Option<&LocalVar<'src>> {
+        self.find_decl(name).map(|(var, _)| var)
+    }
+
+    fn expr_stmt(&mut self) -> Result<Node<'src>, CompileError> {
         if self.consume(";") {
-            return Node::new(NodeKind::Block(vec![]));
+            return Ok(Node::new(NodeKind::Block(vec![]), self.prev_span()));
         }
 
-        let node = Node::new(NodeKind::ExprStmt(Box::new(self.expr())));
-        self.expect(";");
+        let span = self.tokens[self.cursor].span();
+        let node = Node::new(NodeKind::ExprStmt(Box::new(self.expr()?)), span);
+        self.expect(";")?;
 
-        node
+        Ok(node)
     }
 
-    fn expr(&mut self) -> Node<'src> {
-        self.assign()
+    fn expr(&mut self) -> Result<Node<'src>, CompileError> {
+        let mut node = self.assign()?;
+        while self.consume(",") {
+            let span = self.prev_span();
+            node = Node::binop(BinOp::Comma, node, self.assign()?, span);
+        }
+
+        Ok(node)
     }
 
-    fn assign(&mut self) -> Node<'src> {
-        let mut node = self.equality();
+    fn assign(&mut self) -> Result<Node<'src>, CompileError> {
+        let mut node = self.conditional()?;
 
         if self.consume("=") {
-            node = Node::new(NodeKind::BinOp {
-                op: BinOp::Assign,
-                lhs: Box::new(node),
-                rhs: Box::new(self.assign()),
-            })
+            let span = self.prev_span();
+            node = Node::binop(BinOp::Assign, node, self.assign()?, span);
         }
 
-        node
+        for (punct, op) in [
+            ("+=", BinOp::Add),
+            ("-=", BinOp::Sub),
+            ("*=", BinOp::Mul),
+            ("/=", BinOp::Div),
+            ("%=", BinOp::Mod),
+            ("<<=", BinOp::Shl),
+            (">>=", BinOp::Shr),
+            ("&=", BinOp::BitAnd),
+            ("|=", BinOp::BitOr),
+            ("^=", BinOp::BitXor),
+        ] {
+            if self.consume(punct) {
+                let span = self.prev_span();
+                return Ok(Node::new(
+                    NodeKind::AssignOp {
+                        op,
+                        lhs: Box::new(node),
+                        rhs: Box::new(self.assign()?),
+                    },
+                    span,
+                ));
+            }
+        }
+
+        Ok(node)
     }
 
-    fn equality(&mut self) -> Node<'src> {
-        let mut node = self.relational();
+    fn conditional(&mut self) -> Result<Node<'src>, CompileError> {
+        let cond = self.logor()?;
+        if !self.consume("?") {
+            return Ok(cond);
+        }
+        let span = self.prev_span();
+
+        let then = self.expr()?;
+        self.expect(":")?;
+        let els = self.conditional()?;
+
+        Ok(Node::new(
+            NodeKind::Cond {
+                cond: Box::new(cond),
+                then: Box::new(then),
+                els: Box::new(els),
+            },
+            span,
+        ))
+    }
+
+    fn logor(&mut self) -> Result<Node<'src>, CompileError> {
+        let mut node = self.logand()?;
+        while self.consume("||") {
+            let span = self.prev_span();
+            node = Node::binop(BinOp::LogOr, node, self.logand()?, span);
+        }
+
+        Ok(node)
+    }
+
+    fn logand(&mut self) -> Result<Node<'src>, CompileError> {
+        let mut node = self.bitor()?;
+        while self.consume("&&") {
+            let span = self.prev_span();
+            node = Node::binop(BinOp::LogAnd, node, self.bitor()?, span);
+        }
+
+        Ok(node)
+    }
+
+    fn bitor(&mut self) -> Result<Node<'src>, CompileError> {
+        let mut node = self.bitxor()?;
+        while self.consume("|") {
+            let span = self.prev_span();
+            node = Node::binop(BinOp::BitOr, node, self.bitxor()?, span);
+        }
+
+        Ok(node)
+    }
+
+    fn bitxor(&mut self) -> Result<Node<'src>, CompileError> {
+        let mut node = self.bitand()?;
+        while self.consume("^") {
+            let span = self.prev_span();
+            node = Node::binop(BinOp::BitXor, node, self.bitand()?, span);
+        }
+
+        Ok(node)
+    }
+
+    fn bitand(&mut self) -> Result<Node<'src>, CompileError> {
+        let mut node = self.equality()?;
+        while self.consume("&") {
+            let span = self.prev_span();
+            node = Node::binop(BinOp::BitAnd, node, self.equality()?, span);
+        }
+
+        Ok(node)
+    }
+
+    fn equality(&mut self) -> Result<Node<'src>, CompileError> {
+        let mut node = self.relational()?;
 
         loop {
             if self.consume("==") {
-                node = Node::new(NodeKind::BinOp {
-                    op: BinOp::Eq,
-                    lhs: Box::new(node),
-                    rhs: Box::new(self.relational()),
-                });
+                let span = self.prev_span();
+                node = Node::binop(BinOp::Eq, node, self.relational()?, span);
             } else if self.consume("!=") {
-                node = Node::new(NodeKind::BinOp {
-                    op: BinOp::Ne,
-                    lhs: Box::new(node),
-                    rhs: Box::new(self.relational()),
-                });
+                let span = self.prev_span();
+                node = Node::binop(BinOp::Ne, node, self.relational()?, span);
             } else {
-                return node;
+                return Ok(node);
             }
         }
     }
 
-    fn relational(&mut self) -> Node<'src> {
-        let mut node = self.add();
+    fn relational(&mut self) -> Result<Node<'src>, CompileError> {
+        let mut node = self.shift()?;
 
         loop {
             if self.consume("<") {
-                node = Node::new(NodeKind::BinOp {
-                    op: BinOp::Lt,
-                    lhs: Box::new(node),
-                    rhs: Box::new(self.add()),
-                });
+                let span = self.prev_span();
+                node = Node::binop(BinOp::Lt, node, self.shift()?, span);
             } else if self.consume("<=") {
-                node = Node::new(NodeKind::BinOp {
-                    op: BinOp::Le,
-                    lhs: Box::new(node),
-                    rhs: Box::new(self.add()),
-                });
+                let span = self.prev_span();
+                node = Node::binop(BinOp::Le, node, self.shift()?, span);
             } else if self.consume(">") {
-                node = Node::new(NodeKind::BinOp {
-                    op: BinOp::Lt,
-                    lhs: Box::new(self.add()),
-                    rhs: Box::new(node),
-                });
+                let span = self.prev_span();
+                node = Node::binop(BinOp::Lt, self.shift()?, node, span);
             } else if self.consume(">=") {
-                node = Node::new(NodeKind::BinOp {
-                    op: BinOp::Le,
-                    lhs: Box::new(self.add()),
-                    rhs: Box::new(node),
-                });
+                let span = self.prev_span();
+                node = Node::binop(BinOp::Le, self.shift()?, node, span);
+            } else {
+                return Ok(node);
+            }
+        }
+    }
+
+    fn shift(&mut self) -> Result<Node<'src>, CompileError> {
+        let mut node = self.add()?;
+        loop {
+            if self.consume("<<") {
+                let span = self.prev_span();
+                node = Node::binop(BinOp::Shl, node, self.add()?, span);
+            } else if self.consume(">>") {
+                let span = self.prev_span();
+                node = Node::binop(BinOp::Shr, node, self.add()?, span);
             } else {
-                return node;
+                return Ok(node);
             }
         }
     }
 
-    fn add(&mut self) -> Node<'src> {
-        let mut node = self.mul();
+    fn add(&mut self) -> Result<Node<'src>, CompileError> {
+        let mut node = self.mul()?;
         loop {
             if self.consume("+") {
-                node = Node::new(NodeKind::BinOp {
-                    op: BinOp::Add,
-                    lhs: Box::new(node),
-                    rhs: Box::new(self.mul()),
-                });
+                let span = self.prev_span();
+                node = Node::binop(BinOp::Add, node, self.mul()?, span);
             } else if self.consume("-") {
-                node = Node::new(NodeKind::BinOp {
-                    op: BinOp::Sub,
-                    lhs: Box::new(node),
-                    rhs: Box::new(self.mul()),
-                });
+                let span = self.prev_span();
+                node = Node::binop(BinOp::Sub, node, self.mul()?, span);
             } else {
-                return node;
+                return Ok(node);
             }
         }
     }
 
-    fn mul(&mut self) -> Node<'src> {
-        let mut node = self.unary();
+    fn mul(&mut self) -> Result<Node<'src>, CompileError> {
+        let mut node = self.unary()?;
         loop {
             if self.consume("*") {
-                node = Node::new(NodeKind::BinOp {
-                    op: BinOp::Mul,
-                    lhs: Box::new(node),
-                    rhs: Box::new(self.unary()),
-                });
+                let span = self.prev_span();
+                node = Node::binop(BinOp::Mul, node, self.unary()?, span);
             } else if self.consume("/") {
-                node = Node::new(NodeKind::BinOp {
-                    op: BinOp::Div,
-                    lhs: Box::new(node),
-                    rhs: Box::new(self.unary()),
-                });
+                let span = self.prev_span();
+                node = Node::binop(BinOp::Div, node, self.unary()?, span);
+            } else if self.consume("%") {
+                let span = self.prev_span();
+                node = Node::binop(BinOp::Mod, node, self.unary()?, span);
             } else {
-                return node;
+                return Ok(node);
             }
         }
     }
 
-    fn unary(&mut self) -> Node<'src> {
+    fn unary(&mut self) -> Result<Node<'src>, CompileError> {
+        // +x is short for 0+x, so the operand is promoted and not an lvalue
         if self.consume("+") {
-            return self.unary();
+            let span = self.prev_span();
+            let zero = Node::new(NodeKind::Num(0), span.clone());
+            return Ok(Node::binop(BinOp::Add, zero, self.unary()?, span));
+        }
+
+        // ++x is short for x+=1
+        if self.consume("++") {
+            let span = self.prev_span();
+            return Ok(Node::new(
+                NodeKind::AssignOp {
+                    op: BinOp::Add,
+                    lhs: Box::new(self.unary()?),
+                    rhs: Box::new(Node::new(NodeKind::Num(1), span.clone())),
+                },
+                span,
+            ));
+        }
+
+        if self.consume("--") {
+            let span = self.prev_span();
+            return Ok(Node::new(
+                NodeKind::AssignOp {
+                    op: BinOp::Sub,
+                    lhs: Box::new(self.unary()?),
+                    rhs: Box::new(Node::new(NodeKind::Num(1), span.clone())),
+                },
+                span,
+            ));
         }
 
         if self.consume("-") {
-            return Node::new(NodeKind::BinOp {
-                op: BinOp::Sub,
-                lhs: Box::new(Node::new(NodeKind::Num(0))),
-                rhs: Box::new(self.unary()),
-            });
+            let span = self.prev_span();
+            let zero = Node::new(NodeKind::Num(0), span.clone());
+            return Ok(Node::binop(BinOp::Sub, zero, self.unary()?, span));
+        }
+
+        if self.consume("~") {
+            let span = self.prev_span();
+            return Ok(Node::new(NodeKind::BitNot(Box::new(self.unary()?)), span));
         }
 
         if self.consume("&") {
-            return Node::new(NodeKind::Addr(Box::new(self.unary())));
+            let span = self.prev_span();
+            return Ok(Node::new(NodeKind::Addr(Box::new(self.unary()?)), span));
         }
 
         if self.consume("*") {
-            return Node::new(NodeKind::Deref(Box::new(self.unary())));
+            let span = self.prev_span();
+            return Ok(Node::new(NodeKind::Deref(Box::new(self.unary()?)), span));
+        }
+
+        if self.consume("sizeof") {
+            let span = self.prev_span();
+            if self.tokens[self.cursor].raw_str == "(" && self.is_type_start(self.cursor + 1) {
+                self.expect("(")?;
+                let ctype = self.typename()?;
+                self.expect(")")?;
+                if !ctype.has_size() {
+                    return Err(CompileError {
+                        message: "関数に sizeof は適用できません".to_string(),
+                        span,
+                    });
+                }
+                return Ok(Node::new(NodeKind::Num(ctype.size() as i32), span));
+            }
+
+            return Ok(Node::new(NodeKind::Sizeof(Box::new(self.unary()?)), span));
         }
 
-        self.primary()
+        self.postfix()
+    }
+
+    fn postfix(&mut self) -> Result<Node<'src>, CompileError> {
+        let mut node = self.primary()?;
+
+        loop {
+            if self.consume("[") {
+                // x[y] is short for *(x+y)
+                let span = self.prev_span();
+                let index = self.expr()?;
+                self.expect("]")?;
+                let addr = Node::binop(BinOp::Add, node, index, span.clone());
+                node = Node::new(NodeKind::Deref(Box::new(addr)), span);
+            } else if self.consume("++") {
+                // x++ is short for (x+=1)-1
+                node = increment(node, BinOp::Add, BinOp::Sub, self.prev_span())?;
+            } else if self.consume("--") {
+                // x-- is short for (x-=1)+1
+                node = increment(node, BinOp::Sub, BinOp::Add, self.prev_span())?;
+            } else {
+                return Ok(node);
+            }
+        }
     }
 
-    fn primary(&mut self) -> Node<'src> {
+    fn primary(&mut self) -> Result<Node<'src>, CompileError> {
         if self.consume("(") {
-            let node = self.expr();
-            self.expect(")");
-            return node;
+            let node = self.expr()?;
+            self.expect(")")?;
+            return Ok(node);
+        }
+
+        if self.consume("_Generic") {
+            return self.generic_selection(self.prev_span());
+        }
+
+        if self.consume_builtin("__builtin_putchar") {
+            let span = self.prev_span();
+            self.expect("(")?;
+            let node = self.expr()?;
+            self.expect(")")?;
+            return Ok(Node::new(NodeKind::Putchar(Box::new(node)), span));
+        }
+
+        if self.consume_builtin("__builtin_getchar") {
+            let span = self.prev_span();
+            self.expect("(")?;
+            self.expect(")")?;
+            return Ok(Node::new(NodeKind::Getchar, span));
         }
 
         let token = &self.tokens[self.cursor];
-        if token.kind == TokenKind::Ident {
-            self.locals.push(token.raw_str);
+        if token.kind == TokenKind::Ident && self.tokens[self.cursor + 1].raw_str == "(" {
+            return self.funcall();
+        }
 
+        let token = &self.tokens[self.cursor];
+        let span = token.span();
+        if token.kind == TokenKind::Ident {
+            let name = token.raw_str;
+            if let Some(var) = self.find_var(name).cloned() {
+                self.cursor += 1;
+                return Ok(Node::new(NodeKind::Var(var), span));
+            }
+            if let Some(var) = self.globals.iter().find(|var| var.name == name).cloned() {
+                self.cursor += 1;
+                return Ok(Node::new(NodeKind::GlobalVar(var), span));
+            }
+            if self.functions.contains(&name) {
+                self.cursor += 1;
+                return Ok(Node::new(NodeKind::Func(name), span));
+            }
+            return Err(self.error_at("宣言されていない変数です"));
+        }
+        if let TokenKind::Str(value) = &token.kind {
+            let value = value.clone();
             self.cursor += 1;
+            return Ok(Node::new(NodeKind::Str(value), span));
+        }
+
+        Ok(Node::new(NodeKind::Num(self.expect_number()?), span))
+    }
+
+    fn funcall(&mut self) -> Result<Node<'src>, CompileError> {
+        let token = &self.tokens[self.cursor];
+        let (name, span) = (token.raw_str, token.span());
+        self.cursor += 1;
+        self.expect("(")?;
 
-            return Node::new(NodeKind::Var(token.raw_str));
+        let mut args = vec![];
+        if !self.consume(")") {
+            loop {
+                if args.len() == 8 {
+                    return Err(self.error_at("引数は 8 個までです"));
+                }
+                args.push(self.assign()?);
+
+                if self.consume(")") {
+                    break;
+                }
+                self.expect(",")?;
+            }
+        }
+
+        self.is_leaf = false;
+        Ok(Node::new(NodeKind::FuncCall { name, args }, span))
+    }
+
+    fn generic_selection(&mut self, span: Range<usize>) -> Result<Node<'src>, CompileError> {
+        self.expect("(")?;
+        let control = self.assign()?;
+
+        let mut assocs = vec![];
+        let mut default = None;
+        while !self.consume(")") {
+            self.expect(",")?;
+
+            if self.consume("default") {
+                self.expect(":")?;
+                default = Some(Box::new(self.assign()?));
+                continue;
+            }
+
+            let ctype = self.typename()?;
+            self.expect(":")?;
+            assocs.push((ctype, self.assign()?));
         }
 
-        Node::new(NodeKind::Num(self.expect_number()))
+        Ok(Node::new(
+            NodeKind::Generic {
+                control: Box::new(control),
+                assocs,
+                default,
+            },
+            span,
+        ))
+    }
+
+    fn is_typename(&self) -> bool {
+        let token = &self.tokens[self.cursor];
+        (token.kind == TokenKind::Reserved && token.raw_str == "inline")
+            || self.is_type_start(self.cursor)
     }
+
+    /// Whether a type specifier starts at `pos`.
+    fn is_type_start(&self, pos: usize) -> bool {
+        let token = &self.tokens[pos];
+        match token.kind {
+            TokenKind::Reserved => {
+                matches!(token.raw_str, "int" | "char" | "signed" | "unsigned")
+            }
+            TokenKind::Ident => matches!(token.raw_str, "typeof" | "__typeof__"),
+            _ => false,
+        }
+    }
+
+    fn declspec(&mut self) -> Result<CType, CompileError> {
+        if self.consume_builtin("typeof") || self.consume_builtin("__typeof__") {
+            self.expect("(")?;
+            let ctype = if self.is_type_start(self.cursor) {
+                self.typename()?
+            } else {
+                // Only the type is needed, so the operand is never evaluated
+                type_of(self.expr()?)?
+            };
+            self.expect(")")?;
+            return Ok(ctype);
+        }
+
+        // Plain char is signed, so signed only matters for readability
+        if self.consume("signed") {
+            if self.consume("char") {
+                return Ok(CType::Char);
+            }
+            self.consume("int");
+            return Ok(CType::Int);
+        }
+        // There is no unsigned int yet
+        if self.consume("unsigned") {
+            self.expect("char")?;
+            return Ok(CType::UChar);
+        }
+
+        if self.consume("char") {
+            return Ok(CType::Char);
+        }
+        self.expect("int")?;
+        Ok(CType::Int)
+    }
+
+    fn pointers(&mut self, mut ctype: CType) -> CType {
+        while self.consume("*") {
+            ctype = CType::Ptr(Box::new(ctype));
+        }
+
+        ctype
+    }
+
+    /// Returns the declared name, its byte offset and its type.
+    fn declarator(&mut self, base: CType) -> Result<(&'src str, usize, CType), CompileError> {
+        let ctype = self.pointers(base);
+        let pos = self.tokens[self.cursor].pos;
+        let name = self.expect_ident()?;
+        let ctype = self.type_suffix(ctype)?;
+
+        Ok((name, pos, ctype))
+    }
+
+    fn type_suffix(&mut self, ctype: CType) -> Result<CType, CompileError> {
+        if self.consume("[") {
+            let span = self.tokens[self.cursor].span();
+            let mut len = self.conditional()?;
+            fold_constants(&mut len)?;
+            let NodeKind::Num(len @ 0..) = len.kind else {
+                return Err(CompileError {
+                    message: "配列の長さは 0 以上の定数でなければなりません".to_string(),
+                    span,
+                });
+            };
+            self.expect("]")?;
+            let ctype = self.type_suffix(ctype)?;
+            return Ok(CType::Array(Box::new(ctype), len as usize));
+        }
+
+        Ok(ctype)
+    }
+
+    fn typename(&mut self) -> Result<CType, CompileError> {
+        let base = self.declspec()?;
+        Ok(self.pointers(base))
+    }
+}
+
+fn unsized_variable(name: &str, pos: usize) -> CompileError {
+    CompileError {
+        message: "関数型の変数は宣言できません".to_string(),
+        span: pos..pos + name.len(),
+    }
+}
+
+/// Update `node` by one with `op`, then undo it on the result so the
+/// expression yields the old value.
+fn increment<'src>(
+    node: Node<'src>,
+    op: BinOp,
+    undo: BinOp,
+    span: Range<usize>,
+) -> Result<Node<'src>, CompileError> {
+    let one = Node::new(NodeKind::Num(1), span.clone());
+    let update = Node::new(
+        NodeKind::AssignOp {
+            op,
+            lhs: Box::new(node),
+            rhs: Box::new(one.clone()),
+        },
+        span.clone(),
+    );
+
+    // The undone value is converted back, so a char at its limit
+    // yields its old value rather than one past it
+    let ctype = type_of(update.clone())?;
+    let node = Node::binop(undo, update, one, span.clone());
+
+    Ok(Node::new(NodeKind::Cast(Box::new(node), ctype), span))
 }
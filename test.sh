@@ -1,10 +1,20 @@
 #!/bin/bash
+cat <<EOF | riscv64-elf-gcc -xc -c -o tmp2.o -
+int ret3() { return 3; }
+int ret5() { return 5; }
+int add2(int x, int y) { return x+y; }
+int sub2(int x, int y) { return x-y; }
+int add6(int a, int b, int c, int d, int e, int f) { return a+b+c+d+e+f; }
+int add8(int a, int b, int c, int d, int e, int f, int g, int h) { return a+b+c+d+e+f+g+h; }
+EOF
+
 assert() {
   expected="$1"
   input="$2"
+  shift 2
 
-  RUSTFLAGS=-Awarnings cargo run -q -- "$input" > tmp.s
-  riscv64-elf-gcc -o tmp tmp.s
+  RUSTFLAGS=-Awarnings cargo run -q -- "$@" "$input" > tmp.s
+  riscv64-elf-gcc -o tmp tmp.s tmp2.o
   qemu-riscv64 ./tmp
   actual="$?"
 
@@ -16,65 +26,853 @@ assert() {
   fi
 }
 
-assert 0 '{ return 0; }'
-assert 42 '{ return 42; }'
-assert 21 '{ return 5+20-4; }'
-assert 41 '{ return  12 + 34 - 5 ; }'
-assert 47 '{ return 5+6*7; }'
-assert 15 '{ return 5*(9-6); }'
-assert 4 '{ return (3+5)/2; }'
-assert 10 '{ return -10+20; }'
-assert 10 '{ return - -10; }'
-assert 10 '{ return - - +10; }'
-assert 0 '{ return 0==1; }'
-assert 1 '{ return 42==42; }'
-assert 1 '{ return 0!=1; }'
-assert 0 '{ return 42!=42; }'
-assert 1 '{ return 0<1; }'
-assert 0 '{ return 1<1; }'
-assert 0 '{ return 2<1; }'
-assert 1 '{ return 0<=1; }'
-assert 1 '{ return 1<=1; }'
-assert 0 '{ return 2<=1; }'
-assert 1 '{ return 1>0; }'
-assert 0 '{ return 1>1; }'
-assert 0 '{ return 1>2; }'
-assert 1 '{ return 1>=0; }'
-assert 1 '{ return 1>=1; }'
-assert 0 '{ return 1>=2; }'
-assert 3 '{ a=3; return a; }'
-assert 8 '{ a=3; z=5; return a+z; }'
-assert 3 '{ a=3; return a; }'
-assert 8 '{ a=3; z=5; return a+z; }'
-assert 6 '{ a=b=3; return a+b; }'
-assert 3 '{ foo=3; return foo; }'
-assert 8 '{ foo123=3; bar=5; return foo123+bar; }'
-assert 1 '{ return 1; 2; 3; }'
-assert 2 '{ 1; return 2; 3; }'
-assert 3 '{ 1; 2; return 3; }'
-assert 3 '{ {1; {2;} return 3;} }'
-assert 5 '{ ;;; return 5; }'
-
-assert 3 '{ if (0) return 2; return 3; }'
-assert 3 '{ if (1-1) return 2; return 3; }'
-assert 2 '{ if (1) return 2; return 3; }'
-assert 2 '{ if (2-1) return 2; return 3; }'
-assert 4 '{ if (0) { 1; 2; return 3; } else { return 4; } }'
-assert 3 '{ if (1) { 1; 2; return 3; } else { return 4; } }'
-
-assert 55 '{ i=0; j=0; for (i=0; i<=10; i=i+1) j=i+j; return j; }'
-assert 3 '{ for (;;) {return 3;} return 5; }'
-
-assert 10 '{ i=0; while(i<10) { i=i+1; } return i; }'
-
-assert 3 '{ x=3; return *&x; }'
-assert 3 '{ x=3; y=&x; z=&y; return **z; }'
-assert 5 '{ x=3; y=5; return *(&x+1); }'
-assert 3 '{ x=3; y=5; return *(&y-1); }'
-assert 5 '{ x=3; y=5; return *(&x-(-1)); }'
-assert 5 '{ x=3; y=&x; *y=5; return x; }'
-assert 7 '{ x=3; y=5; *(&x+1)=7; return y; }'
-assert 7 '{ x=3; y=5; *(&y-2+1)=7; return x; }'
-assert 5 '{ x=3; return (&x+2)-&x+3; }'
+assert_stdout() {
+  expected="$1"
+  input="$2"
+  shift 2
+
+  RUSTFLAGS=-Awarnings cargo run -q -- "$@" "$input" > tmp.s
+  riscv64-elf-gcc -o tmp tmp.s tmp2.o
+  actual=$(qemu-riscv64 ./tmp < /dev/null)
+
+  if [ "$actual" = "$expected" ]; then
+    echo "$input => '$actual'"
+  else
+    echo "$input => '$expected' expected, but got '$actual'"
+    exit 1
+  fi
+}
+
+assert_error() {
+  pattern="$1"
+  input="$2"
+  shift 2
+
+  if output=$(RUSTFLAGS=-Awarnings RUST_BACKTRACE=0 cargo run -q -- "$@" "$input" 2>&1 >/dev/null); then
+    echo "$input => error expected, but compiled"
+    exit 1
+  fi
+  if echo "$output" | grep -q -- "$pattern"; then
+    echo "$input => error '$pattern'"
+  else
+    echo "$input => error '$pattern' expected, but got: $output"
+    exit 1
+  fi
+}
+
+# Check the exit status of the compiler itself, e.g. 1 for a reported
+# error rather than 101 for a panic.
+assert_compile_status() {
+  expected="$1"
+  input="$2"
+  shift 2
+
+  RUSTFLAGS=-Awarnings RUST_BACKTRACE=0 cargo run -q -- "$@" "$input" >/dev/null 2>&1
+  actual="$?"
+
+  if [ "$actual" = "$expected" ]; then
+    echo "$input => compiler exits with $actual"
+  else
+    echo "$input => compiler exit $expected expected, but got $actual"
+    exit 1
+  fi
+}
+
+assert_warning() {
+  pattern="$1"
+  input="$2"
+  shift 2
+
+  if ! output=$(RUSTFLAGS=-Awarnings cargo run -q -- "$@" "$input" 2>&1 >/dev/null); then
+    echo "$input => warning expected, but failed: $output"
+    exit 1
+  fi
+  if echo "$output" | grep -q -- "$pattern"; then
+    echo "$input => warning '$pattern'"
+  else
+    echo "$input => warning '$pattern' expected, but got: $output"
+    exit 1
+  fi
+}
+
+assert_not_warning() {
+  pattern="$1"
+  input="$2"
+  shift 2
+
+  if ! output=$(RUSTFLAGS=-Awarnings cargo run -q -- "$@" "$input" 2>&1 >/dev/null); then
+    echo "$input => compile expected, but failed: $output"
+    exit 1
+  fi
+  if echo "$output" | grep -q -- "$pattern"; then
+    echo "$input => warning '$pattern' not expected, but got: $output"
+    exit 1
+  else
+    echo "$input => no warning '$pattern'"
+  fi
+}
+
+assert_emit() {
+  pattern="$1"
+  shift
+
+  output=$(RUSTFLAGS=-Awarnings cargo run -q -- "$@")
+  if echo "$output" | grep -q -- "$pattern"; then
+    echo "$* => emits '$pattern'"
+  else
+    echo "$* => '$pattern' expected, but not emitted"
+    exit 1
+  fi
+}
+
+# Checks that a line matching the second pattern directly follows one
+# matching the first.
+assert_emit_next() {
+  first="$1"
+  second="$2"
+  shift 2
+
+  output=$(RUSTFLAGS=-Awarnings cargo run -q -- "$@")
+  if echo "$output" | grep -A1 -- "$first" | grep -q -- "$second"; then
+    echo "$* => emits '$second' after '$first'"
+  else
+    echo "$* => '$second' expected after '$first', but not emitted"
+    exit 1
+  fi
+}
+
+assert_not_emit() {
+  pattern="$1"
+  shift
+
+  output=$(RUSTFLAGS=-Awarnings cargo run -q -- "$@")
+  if echo "$output" | grep -q -- "$pattern"; then
+    echo "$* => '$pattern' not expected, but emitted"
+    exit 1
+  else
+    echo "$* => does not emit '$pattern'"
+  fi
+}
+
+# Compare the assembly generated for golden/NAME.c with golden/NAME.s.
+# Run with UPDATE_GOLDEN=1 to rewrite the .s files instead.
+assert_golden() {
+  source="$1"
+  expected="${source%.c}.s"
+
+  output=$(RUSTFLAGS=-Awarnings cargo run -q -- "$(cat "$source")")
+  if [ -n "$UPDATE_GOLDEN" ]; then
+    echo "$output" > "$expected"
+    echo "$source => updated $expected"
+  elif [ "$output" = "$(cat "$expected")" ]; then
+    echo "$source => matches $expected"
+  else
+    echo "$source => differs from $expected"
+    diff <(echo "$output") "$expected"
+    exit 1
+  fi
+}
+
+assert 0 'int main() { return 0; }'
+assert 42 'int main() { return 42; }'
+assert 21 'int main() { return 5+20-4; }'
+assert 41 'int main() { return  12 + 34 - 5 ; }'
+assert 47 'int main() { return 5+6*7; }'
+assert 15 'int main() { return 5*(9-6); }'
+assert 4 'int main() { return (3+5)/2; }'
+assert 10 'int main() { return -10+20; }'
+assert 10 'int main() { return - -10; }'
+assert 10 'int main() { return - - +10; }'
+assert 4 'int main() { char c; return sizeof(+c); }'
+assert 4 'int main() { char c; return sizeof(-c); }'
+assert 1 'int main() { char c; return _Generic(+c, int: 1, char: 2); }'
+assert 8 'int main() { int *p; return sizeof(+p); }'
+assert 3 'int main() { int x=3; int *p=&x; return *+p; }'
+assert_error '左辺値が必要です' 'int main() { int x; +x = 1; return x; }'
+assert 0 'int main() { return 0==1; }'
+assert 1 'int main() { return 42==42; }'
+assert 1 'int main() { return 0!=1; }'
+assert 0 'int main() { return 42!=42; }'
+assert 1 'int main() { return 0<1; }'
+assert 0 'int main() { return 1<1; }'
+assert 0 'int main() { return 2<1; }'
+assert 1 'int main() { return 0<=1; }'
+assert 1 'int main() { return 1<=1; }'
+assert 0 'int main() { return 2<=1; }'
+assert 1 'int main() { return 1>0; }'
+assert 0 'int main() { return 1>1; }'
+assert 0 'int main() { return 1>2; }'
+assert 1 'int main() { return 1>=0; }'
+assert 1 'int main() { return 1>=1; }'
+assert 0 'int main() { return 1>=2; }'
+assert 3 'int main() { int a; a=3; return a; }'
+assert 8 'int main() { int a; int z; a=3; z=5; return a+z; }'
+assert 3 'int main() { int a; a=3; return a; }'
+assert 8 'int main() { int a; int z; a=3; z=5; return a+z; }'
+assert 6 'int main() { int a; int b; a=b=3; return a+b; }'
+assert 3 'int main() { int foo; foo=3; return foo; }'
+assert 8 'int main() { int foo123=3; int bar=5; return foo123+bar; }'
+assert 1 'int main() { return 1; 2; 3; }'
+assert 2 'int main() { 1; return 2; 3; }'
+assert 3 'int main() { 1; 2; return 3; }'
+assert 3 'int main() { {1; {2;} return 3;} }'
+assert 5 'int main() { ;;; return 5; }'
+
+assert 3 'int main() { if (0) return 2; return 3; }'
+assert 3 'int main() { if (1-1) return 2; return 3; }'
+assert 2 'int main() { if (1) return 2; return 3; }'
+assert 2 'int main() { if (2-1) return 2; return 3; }'
+assert 4 'int main() { if (0) { 1; 2; return 3; } else { return 4; } }'
+assert 3 'int main() { if (1) { 1; 2; return 3; } else { return 4; } }'
+
+assert 55 'int main() { int i=0; int j=0; for (i=0; i<=10; i=i+1) j=i+j; return j; }'
+assert 3 'int main() { for (;;) {return 3;} return 5; }'
+
+assert 10 'int main() { int i=0; while(i<10) { i=i+1; } return i; }'
+
+assert 3 'int main() { int x=3; return *&x; }'
+assert 3 'int main() { int x=3; int *y=&x; return *y; }'
+assert 3 'int main() { int x=5; int *y=&x; *y=3; return x; }'
+assert 3 'int main() { int x=5; *&x=3; return *&*&x; }'
+assert 3 'int main() { int x=3; int *y=&x; int **z=&y; return **z; }'
+assert 5 'int main() { int x=3; int y=5; return *(&x-1); }'
+assert 3 'int main() { int x=3; int y=5; return *(&y+1); }'
+assert 5 'int main() { int x=3; int y=5; return *(&x+(-1)); }'
+assert 5 'int main() { int x=3; int *y=&x; *y=5; return x; }'
+assert 7 'int main() { int x=3; int y=5; *(&x-1)=7; return y; }'
+assert 7 'int main() { int x=3; int y=5; *(&y+2-1)=7; return x; }'
+assert 5 'int main() { int x=3; return (&x+2)-&x+3; }'
+assert 2 'int main() { int x=0; return &x+2-&x; }'
+assert 3 'int main() { int x=3; return *(1+&x-1); }'
+assert_error 'ポインタは乗除算のオペランドにできません' 'int main() { int x=1; return &x * 2; }'
+assert_error 'ポインタは乗除算のオペランドにできません' 'int main() { int x=1; return 2 / &x; }'
+assert_error 'ポインタ同士の加算はできません' 'int main() { int x=1; return &x + &x; }'
+
+assert 32 'int main() { return ret32(); } int ret32() { return 32; }'
+assert 7 'int main() { return add(3,4); } int add(int a, int b) { return a+b; }'
+assert 1 'int main() { return sub(4,3); } int sub(int a, int b) { return a-b; }'
+assert 55 'int main() { return fib(9); } int fib(int x) { if (x<=1) return 1; return fib(x-1) + fib(x-2); }'
+assert 21 'int main() { return add6(1,2,3,4,5,6); } int add6(int a, int b, int c, int d, int e, int f) { return a+b+c+d+e+f; }'
+assert 36 'int main() { return sum8(1,2,3,4,5,6,7,8); } int sum8(int a, int b, int c, int d, int e, int f, int g, int h) { return a+b+c+d+e+f+g+h; }'
+assert_emit 'functions: \[\]' --stop-after parse ''
+assert_emit 'add:' 'int main() { return add(3,4); } int add(int a, int b) { return a+b; }'
+assert_emit 'sw a1, -8(fp)' 'int add(int a, int b) { return a+b; }'
+assert 5 'int main() { int x; x=5; return x; }'
+assert 8 'int main() { int a, b; a=3; b=5; return a+b; }'
+assert 6 'int main() { int a=1, *p=&a, b=*p+4; return a+b; }'
+assert 3 'int main() { int x=3, *y=&x, **z=&y; return **z; }'
+assert_error '宣言されていない変数です' 'int main() { return y; }'
+assert_error '宣言されていない変数です' 'int main() { int x; x=1; y=2; return x; }'
+assert_error '変数が二重に宣言されています' 'int main() { int x; int x; return 0; }'
+assert_warning 'キャストなしで整数をポインタに代入しています' 'int main() { int a=4; int *p; p = a / 2; return 0; }'
+assert_not_warning 'キャストなしで整数をポインタに代入しています' 'int main() { int *p; p = 0; return 0; }'
+assert_error "';' が必要ですが、識別子 'y' が見つかりました" 'int main() { int x; x=1 y; }'
+assert_error "')' が必要ですが、数 '2' が見つかりました" 'int main() { return (1 2); }'
+assert_error "'{' が必要ですが、入力の終わり が見つかりました" 'int main()'
+assert_error '識別子ではありません' 'int () { return 0; }'
+
+assert 10 "int main() { return 1'000 - 990; }"
+assert 40 "int main() { return 1'000'000 / 25'000; }"
+assert_emit 'Num(1000)' --stop-after lex "1'000"
+assert_error '^1行目 22列目: 数字区切りの位置が正しくありません$' "int main() { return 1''0; }"
+assert_error '^1行目 23列目: 数字区切りの位置が正しくありません$' "int main() { return 10'; }"
+
+assert 3 'int main() { return ret3(); }'
+assert 5 'int main() { return ret5(); }'
+assert 8 'int main() { return add2(3, 5); }'
+assert 2 'int main() { return sub2(5, 3); }'
+assert 21 'int main() { return add6(1,2,3,4,5,6); }'
+assert 36 'int main() { return add8(1,2,3,4,5,6,7,8); }'
+assert 66 'int main() { return add6(1,2,add6(3,4,5,6,7,8),9,10,11); }'
+assert 136 'int main() { return add6(1,2,add6(3,add6(4,5,6,7,8,9),10,11,12,13),14,15,16); }'
+assert 12 'int main() { int x=1; return x + add2(x, 3) + ret3() + add2(1, ret3()); }'
+assert_emit 'call foo' 'int main() { return foo(1, 2+3); }'
+assert_error '引数は 8 個までです' 'int main() { return foo(1,2,3,4,5,6,7,8,9); }'
+
+assert_stdout 'A' 'int main() { __builtin_putchar(65); return 0; }'
+assert_stdout 'AB' 'int main() { int x=65; __builtin_putchar(x); __builtin_putchar(x+1); return 0; }'
+assert 66 'int main() { return __builtin_putchar(66); }'
+assert_stdout 'A' 'int main() { __builtin_putchar(__builtin_getchar() + 66); return 0; }'
+
+assert 10 'int main() { return _Generic(1, int: 10, default: 20); }'
+assert 20 'int main() { int x=1; return _Generic(&x, int: 10, default: 20); }'
+assert 1 'int main() { int x=1; return _Generic(&x, int*: 1, int: 2); }'
+assert 3 'int main() { int x=1; int *y=&x; return _Generic(&y, int*: 1, int**: 3, int: 2); }'
+assert 5 'int main() { int x=5; return _Generic(x, int: x, default: 0); }'
+
+assert 5 '2+3' --expr
+assert 47 '5+6*7' --expr
+
+assert_emit 'Return' --stop-after parse 'int main() { return 0; }'
+assert_not_emit 'main:' --stop-after parse 'int main() { return 0; }'
+assert_emit 'digraph AST {' --ast-dot --expr '1+2'
+assert_emit 'n3 \[label="Add"\];' --ast-dot --expr '1+2'
+assert_emit 'n3 -> n4 \[label="lhs"\];' --ast-dot --expr '1+2'
+assert_emit 'n3 -> n5 \[label="rhs"\];' --ast-dot --expr '1+2'
+assert_emit 'n4 \[label="Num(1)"\];' --ast-dot --expr '1+2'
+assert_emit 'Reserved' --stop-after lex '{ return 0; }'
+assert_emit 'kind: Reserved, raw_str: "struct"' --stop-after lex 'struct structure'
+assert_emit 'kind: Ident, raw_str: "structure"' --stop-after lex 'struct structure'
+assert_emit 'kind: Ident, raw_str: "returnx"' --stop-after lex 'returnx'
+assert_emit 'ctype: Int' --stop-after type 'int main() { return 0; }'
+assert_emit 'ctype: Ptr(' --stop-after type 'int main() { int x=1; return &x; }'
+assert_not_emit 'ctype: Ptr(' --stop-after type 'int main() { int x=1; return x + 2; }'
+assert_emit 'ctype: Statement' --stop-after type 'int main() { if (1) return 0; }'
+assert_emit 'main:' --stop-after codegen 'int main() { return 0; }'
+
+assert 0 'int main() { return 0; }' -fno-omit-frame-pointer
+assert_emit 'mv fp, sp' -fno-omit-frame-pointer 'int main() { return 0; }'
+assert_emit 'pop fp' -fno-omit-frame-pointer 'int main() { return 0; }'
+assert_not_emit 'mv fp, sp' 'int main() { return 0; }'
+assert_emit 'mv fp, sp' 'int main() { int x=1; return x; }'
+
+assert 3 'int main() { return 3; }' -mno-relax
+assert_emit '.option norelax' -mno-relax 'int main() { return 0; }'
+assert_not_emit '.option norelax' 'int main() { return 0; }'
+
+assert_emit '.type main, @function' 'int main() { return 0; }'
+assert_emit '.size main, .-main' 'int main() { return 0; }'
+
+assert 0 'int main() { int x; return x; }' -ftrivial-auto-var-init=zero
+assert 3 'int main() { int a=1; int b=2; int c; return a+b+c; }' -ftrivial-auto-var-init=zero
+assert_emit 'sd zero, -8(fp)' -ftrivial-auto-var-init=zero 'int main() { int x; return x; }'
+assert_not_emit 'sd zero' 'int main() { int x; return x; }'
+
+assert_emit 'addi sp, sp, -16' 'int main() { int a, b, c; return 0; }'
+assert_emit '# c -> -12(fp)' 'int main() { int a, b, c; return 0; }'
+assert_emit 'addi a0, fp, -12' 'int main() { int a, b, c; c=1; return c; }'
+assert 6 'int main() { int a=1, b=2, c=3; return a+b+c; }'
+assert_emit '# a -> -4(fp)' 'int main() { int a=1; int b=2; return a+b; }'
+assert_emit '# b -> -8(fp)' 'int main() { int a=1; int b=2; return a+b; }'
+
+assert 3 'int main() { int a[2]; a[0]=1; a[1]=2; return a[0]+a[1]; }'
+assert 3 'int main() { int a[2]; *a=1; *(a+1)=2; int *p=a; return *p+*(p+1); }'
+assert 5 'int main() { int a[3]; int i=2; a[i]=5; return a[2]; }'
+assert 5 'int main() { int x[2][3]; int *y=x; *(y+5)=5; return x[1][2]; }'
+assert 4 'int main() { int x[2][3]; x[1][0]=4; return *(*(x+1)); }'
+assert 2 'int main() { int a[2]; a[1]=2; return 1[a]; }'
+assert_emit 'addi sp, sp, -16' 'int main() { int a[3]; return 0; }'
+assert_error '配列には代入できません' 'int main() { int a[2]; int b[2]; a=b; return 0; }'
+assert_error 'ポインタではないものは参照外しできません' 'int main() { int x=1; return x[0]; }'
+
+assert 3 'int main() { int k=0; for (int i=0, j=3; i<j; i=i+1) k=k+1; return k; }'
+assert 6 'int main() { int k=0; for (int i=0, j=3; i<j; i=i+1) k=k+i+j; return k-6; }'
+assert_error '宣言されていない変数です' 'int main() { for (int i=0, j=3; i<j; i=i+1) ; return i; }'
+assert_error '宣言されていない変数です' 'int main() { for (int i=0, j=3; i<j; i=i+1) ; return j; }'
+assert 7 'int main() { int i=7; for (int i=0; i<3; i=i+1) ; return i; }'
+assert 2 'int main() { int x=1; { int x=2; return x; } }'
+assert 1 'int main() { int x=1; { int x=2; } return x; }'
+assert_error '変数が二重に宣言されています' 'int main() { int x=1; int x=2; return x; }'
+
+assert 4 'int main() { int x; return sizeof(x); }'
+assert 4 'int main() { int x; return sizeof x; }'
+assert 4 'int main() { return sizeof(int); }'
+assert 8 'int main() { int *x; return sizeof(x); }'
+assert 8 'int main() { return sizeof(int*); }'
+assert 12 'int main() { int a[3]; return sizeof(a); }'
+assert 24 'int main() { int a[2][3]; return sizeof a; }'
+assert 12 'int main() { int a[2][3]; return sizeof(a[1]); }'
+assert 4 'int main() { int x; return sizeof(sizeof(x)); }'
+assert 1 'int main() { int x=1; sizeof(x=5); return x; }'
+assert 5 'int main() { int x; return sizeof(x) + 1; }'
+assert_emit '# p -> -16(fp)' 'int main() { int a; int *p; return 0; }'
+assert_emit 'lw a0, 0(a0)' 'int main() { int x=1; return x; }'
+assert_emit 'ld a0, 0(a0)' 'int main() { int x=1; int *p=&x; return *p; }'
+
+assert_error '関数に sizeof は適用できません' 'int foo() { return 0; } int main() { return sizeof(foo); }'
+assert 8 'int foo() { return 0; } int main() { return sizeof(&foo); }'
+assert 8 'int main() { return sizeof(&main); }'
+assert 3 'int foo() { return 0; } int main() { int foo=3; return foo; }'
+assert_emit 'la a0, foo' 'int foo() { return 0; } int main() { &foo; return 0; }'
+
+assert 3 '1 + /* hi */ 2' --expr
+assert 2 'int main() { // return 1;
+return 2; }'
+assert 2 'int main() { /* return 1;
+return 3; */ return 2; }'
+assert 4 'int main() { return 8/*/ 2 */ /2; }'
+assert 0 'int main() { return 0; } // trailing'
+assert_error '^1行目 26列目: ブロックコメントが閉じられていません$' 'int main() { return 0; } /* oops'
+assert_error '^                         \^$' 'int main() { return 0; } /* oops'
+assert_error '^3行目 3列目: ブロックコメントが閉じられていません$' 'int main() {
+  // a comment
+  /* oops
+  return 0; }'
+assert_compile_status 1 'int main() { return 0; } /* oops'
+assert_error '^1行目 21列目: トークナイズできません$' 'int main() { return @; }'
+assert_error '^1行目 21列目: 数値が大きすぎます$' 'int main() { return 99999999999; }'
+
+assert 255 '0xFF' --expr
+assert 255 '0Xff' --expr
+assert 15 '0b1111' --expr
+assert 10 '0B1010' --expr
+assert 11 '0777 - 500' --expr
+assert 0 '0' --expr
+assert 10 '10' --expr
+assert 255 "0xf'f" --expr
+assert_error '^1行目 21列目: 数字がありません$' 'int main() { return 0x; }'
+assert_error '^1行目 21列目: 数字がありません$' 'int main() { return 0b; }'
+assert_error '^1行目 21列目: 数値リテラルが正しくありません$' 'int main() { return 08; }'
+assert_error '^1行目 21列目: 数値リテラルが正しくありません$' 'int main() { return 0b12; }'
+
+assert 5 'int main() { int i=0; while (1) { if (i==5) break; i=i+1; } return i; }'
+assert 25 'int main() { int s=0; for (int i=0; i<10; i=i+1) { if (i/2*2==i) continue; s=s+i; } return s; }'
+assert 30 'int main() { int s=0; for (int i=0; i<3; i=i+1) { for (int j=0; j<100; j=j+1) { if (j==10) break; s=s+1; } } return s; }'
+assert 6 'int main() { int s=0; for (int i=0; i<3; i=i+1) { int j=0; while (j<5) { j=j+1; if (j<=2) continue; break; } s=s+j-1; } return s; }'
+assert 3 'int main() { int i=0; for (;;) { i=i+1; if (i==3) break; } return i; }'
+assert 7 'int main() { int i=0; if (1) { if (0) i=1; else i=7; } else i=2; return i; }'
+assert_error 'break はループの中でしか使えません' 'int main() { break; return 0; }'
+assert_error 'continue はループの中でしか使えません' 'int main() { if (1) continue; return 0; }'
+
+assert 1 'int main() { int a=1, b=2; if (a < b) return 1; return 0; }'
+assert 0 'int main() { int a=2, b=2; if (a < b) return 1; return 0; }'
+assert 1 'int main() { int a=2, b=2; if (a <= b) return 1; return 0; }'
+assert 0 'int main() { int a=3, b=2; if (a <= b) return 1; return 0; }'
+assert 1 'int main() { int a=3, b=2; if (a > b) return 1; return 0; }'
+assert 1 'int main() { int a=2, b=2; if (a == b) return 1; return 0; }'
+assert 0 'int main() { int a=2, b=2; if (a != b) return 1; return 0; }'
+assert 4 'int main() { int i=0; while (i != 4) i=i+1; return i; }'
+assert_emit 'bge t0, t1, .L.else.1' 'int main() { int a=1, b=2; if (a < b) return 1; return 0; }'
+assert_not_emit 'slt' 'int main() { int a=1, b=2; if (a < b) return 1; return 0; }'
+assert_emit 'slt' 'int main() { int a=1, b=2; return a < b; }'
+
+assert 1 '7 % 3' --expr
+assert 0 '9 % 3' --expr
+assert 2 '10 % 3 * 2' --expr
+assert 3 '2 * 10 % 17' --expr
+assert 5 '-7 % 3 + 6' --expr
+assert 1 '7 % -3' --expr
+assert 4 'int main() { int x=14, y=5; return x % y; }'
+assert_error 'ポインタは乗除算のオペランドにできません' 'int main() { int x; int *p=&x; return p % 2; }'
+
+assert 1 '1 && 2' --expr
+assert 0 '1 && 0' --expr
+assert 0 '0 && 1' --expr
+assert 1 '0 || 2' --expr
+assert 0 '0 || 0' --expr
+assert 1 '1 || 0' --expr
+assert 1 '1 || 0 && 0' --expr
+assert 1 '2 == 2 && 3 < 4' --expr
+assert 1 'int main() { int x=3; int *p=&x; return p && x; }'
+assert_stdout '' 'int f() { __builtin_putchar(88); return 1; } int main() { return 0 && f(); }'
+assert_stdout '' 'int f() { __builtin_putchar(88); return 1; } int main() { return 1 || f(); }'
+assert_stdout 'X' 'int f() { __builtin_putchar(88); return 1; } int main() { return 1 && f(); }'
+assert_stdout 'X' 'int f() { __builtin_putchar(88); return 1; } int main() { return 0 || f(); }'
+
+assert 3 '(1, 2, 3)' --expr
+assert 5 'int main() { int x=1; int y=(x=5, x); return y; }'
+assert 7 'int main() { int i, j; for (i=0, j=0; i<7; i=i+1, j=j+1) ; return j; }'
+assert 4 'int main() { int x; return sizeof((x, 2)); }'
+assert 8 'int main() { int x; return sizeof((1, &x)); }'
+assert 8 'int main() { int a[3]; return sizeof((0, a)); }'
+assert 3 'int main() { return add2((1, 2), 1); }'
+
+assert_error '関数の中で関数を定義することはできません' 'int main() { int g() { return 1; } return g(); }'
+assert_error '関数の中で関数を定義することはできません' 'int main() { int x; int *g(int a, int b) { return 0; } return 0; }'
+
+assert 2 '0 ? 1 : 2' --expr
+assert 1 '1 ? 1 : 2' --expr
+assert 4 '0 ? 1 : 0 ? 3 : 4' --expr
+assert 3 '0 ? 1 : 1 ? 3 : 4' --expr
+assert 1 '1 ? 1 : 0 ? 3 : 4' --expr
+assert 2 'int main() { int a; int x=0; a = x ? 1 : 2; return a; }'
+assert 5 'int main() { int x=3; return x > 2 ? x + 2 : x - 2; }'
+assert_stdout 'B' 'int main() { 0 ? __builtin_putchar(65) : __builtin_putchar(66); return 0; }'
+assert 7 'int main() { int x=1; int *p=&x; *(x ? p : p) = 7; return x; }'
+
+assert 7 'int main() { int x=2; x+=5; return x; }'
+assert 7 'int main() { int x=2; return x+=5; }'
+assert 3 'int main() { int x=8; x-=5; return x; }'
+assert 24 'int main() { int x=8; x*=3; return x; }'
+assert 4 'int main() { int x=8; x/=2; return x; }'
+assert 2 'int main() { int x=8; x%=3; return x; }'
+assert 6 'int main() { int x=1; int *p=&x; *p+=5; return x; }'
+assert 3 'int main() { int a[3]; a[0]=1; a[1]=2; a[2]=3; int *p=a; p+=2; return *p; }'
+assert 1 'int main() { int a[3]; a[0]=1; a[1]=2; a[2]=3; int *p=a+2; p-=2; return *p; }'
+assert 151 'int main() { int a[3]; int i=0; a[1]=10; a[i=i+1] += 5; return a[1]*10 + i; }'
+assert_stdout 'A' 'int main() { int a[2]; a[0]=0; a[__builtin_putchar(65) - 65] += 1; return 0; }'
+assert_error '配列には代入できません' 'int main() { int a[2]; a+=1; return 0; }'
+assert_error 'ポインタは乗除算のオペランドにできません' 'int main() { int x; int *p=&x; p*=2; return 0; }'
+assert 40 'int main() { int x=5; x <<= 3; return x; }'
+assert 40 'int main() { int x=5; return x <<= 3; }'
+assert 5 'int main() { int x=40; x >>= 3; return x; }'
+assert 2 'int main() { int x=6; x &= 3; return x; }'
+assert 7 'int main() { int x=6; x |= 3; return x; }'
+assert 5 'int main() { int x=6; x ^= 3; return x; }'
+assert 96 'int main() { char c=3; c <<= 5; return c; }'
+assert 64 'int main() { int a[2]; a[1]=1; int i=1; a[i] <<= 6; return a[1]; }'
+assert 2 'int main() { int x=7, y=2; x &= y |= 2; return x; }'
+assert_emit 'ShlAssign' --ast-dot 'int main() { int x; x <<= 1; return 0; }'
+assert_emit 'ShrAssign' --ast-dot 'int main() { int x; x >>= 1; return 0; }'
+assert_emit 'BitAndAssign' --ast-dot 'int main() { int x; x &= 1; return 0; }'
+assert_emit 'BitOrAssign' --ast-dot 'int main() { int x; x |= 1; return 0; }'
+assert_emit 'BitXorAssign' --ast-dot 'int main() { int x; x ^= 1; return 0; }'
+assert_error 'ビット演算のオペランドは整数でなければなりません' 'int main() { int x; int *p=&x; p <<= 1; return 0; }'
+assert_error 'ビット演算のオペランドは整数でなければなりません' 'int main() { int x; int *p=&x; x |= p; return 0; }'
+
+assert_error '左辺値が必要です' 'int main() { 5 = 1; return 0; }'
+assert_error '左辺値が必要です' 'int main() { 5 += 1; return 0; }'
+assert_error '左辺値が必要です' 'int main() { int a=1, b=2; (a+b) -= 1; return 0; }'
+assert_error '左辺値が必要です' 'int main() { int a=1; return &(a+1); }'
+assert 3 'int main() { int a[2]; int i=1; a[1]=0; a[i] += 3; return a[1]; }'
+
+assert 3 'int main() { int x=2; return ++x; }'
+assert 1 'int main() { int x=2; return --x; }'
+assert 2 'int main() { int x=2; return x++; }'
+assert 2 'int main() { int x=2; return x--; }'
+assert 3 'int main() { int x=2; x++; return x; }'
+assert 1 'int main() { int x=2; x--; return x; }'
+assert 25 'int main() { int x=5; int a; a = x++; return a*5 + x - 6; }'
+assert 30 'int main() { int x=5; int a; a = ++x; return a*5 + x - 6; }'
+assert 3 'int main() { int a[3]; a[0]=1; a[1]=3; int *p=a; p++; return *p; }'
+assert 1 'int main() { int a[3]; a[0]=1; a[1]=3; int *p=a; return *p++; }'
+assert 3 'int main() { int a[3]; a[0]=1; a[1]=3; int *p=a; *p++; return *p; }'
+assert 1 'int main() { int a[3]; a[0]=1; a[1]=3; int *p=a+1; --p; return *p; }'
+assert 4 'int main() { int a[2]; int i=1; a[1]=3; a[i]++; return a[1]; }'
+assert 10 'int main() { int s=0; for (int i=0; i<5; i++) s+=i; return s; }'
+assert_error '左辺値が必要です' 'int main() { 5++; return 0; }'
+assert_error '左辺値が必要です' 'int main() { int a=1, b=2; (a+b)++; return 0; }'
+assert_error '左辺値が必要です' 'int main() { ++5; return 0; }'
+assert_emit 'AddAssign' --ast-dot 'int main() { int a; a++; return 0; }'
+
+assert 3 'inline int f() { return 3; } int main() { return f(); }'
+assert 0 'inline int main() { return 0; }'
+assert_error 'inline は関数にしか指定できません' 'int main() { inline int x; return 0; }'
+
+assert 0 'int main() { }'
+assert 0 'int main() { int x=3; x=x+1; }'
+assert 0 'int main() { int x=0; if (x) return 1; }'
+assert_not_emit 'li a0, 0' 'int main() { return 1; }'
+assert 5 'int main() { return 5; }'
+assert_emit 'li a0, 0' 'int main() { }'
+assert_not_emit 'li a0, 0' 'int f() { }'
+
+assert_error '^3行目 10列目: 宣言されていない変数です$' 'int main() {
+  int x;
+  return y;
+}'
+assert_error '^  return y;$' 'int main() {
+  int x;
+  return y;
+}'
+assert_error '^         ^$' 'int main() {
+  int x;
+  return y;
+}'
+assert_error '^1行目 25列目: 数ではありません$' 'int main() { return 1 + ; }'
+assert_error '^	/\* é \*/ return y;$' 'int main() {
+	int x;
+	/* é */ return y;
+}'
+assert_error '^	               ^$' 'int main() {
+	int x;
+	/* é */ return y;
+}'
+
+assert 5 'int main() { typeof(1+1) x = 5; return x; }'
+assert 4 'int main() { typeof(1+1) x = 5; return sizeof(x); }'
+assert 3 'int main() { int y=3; int *p=&y; typeof(p) q = p; return *q; }'
+assert 8 'int main() { int *p; typeof(p) q; return sizeof(q); }'
+assert 1 'int main() { int *p; typeof(p) q; return _Generic(q, int*: 1, int: 2); }'
+assert 12 'int main() { int a[3]; __typeof__(a) b; return sizeof(b); }'
+assert 8 'int main() { typeof(int*) p; return sizeof(p); }'
+assert 4 'int main() { int x; return sizeof(typeof(x)); }'
+assert 1 'int main() { int x=1; typeof(x=5) y; return x; }'
+assert 3 'typeof(1) f() { return 3; } int main() { return f(); }'
+
+assert_error "^1行目 23列目: ';' が必要ですが、予約語 '}' が見つかりました$" 'int main() { return 0 }'
+assert_error '^                      ^$' 'int main() { return 0 }'
+assert_error "^2行目 11列目: ',' が必要ですが、識別子 'y' が見つかりました$" 'int main() {
+  int x=1 y=2;
+}'
+assert_compile_status 1 'int main() { return 0 }'
+assert_compile_status 1 'int main() { return y; }'
+assert_compile_status 0 'int main() { return 0; }'
+
+assert_error '変数が二重に宣言されています' 'int f(int x) { int x=5; return x; } int main() { return f(3); }'
+assert 5 'int f(int x) { { int x=5; return x; } } int main() { return f(3); }'
+assert_warning "^1行目 22列目: 警告: 'x' の宣言が 1行目 11列目 の宣言を隠しています$" 'int f(int x) { { int x=5; return x; } } int main() { return f(3); }' -Wshadow
+assert_warning "^3行目 9列目: 警告: 'y' の宣言が 2行目 7列目 の宣言を隠しています$" 'int main() {
+  int y=1;
+  { int y=2; }
+  return y;
+}' -Wshadow
+assert_warning "'i' の宣言が" 'int main() { int i=0; for (int i=0; i<3; i++) ; return i; }' -Wshadow
+assert_not_warning '隠しています' 'int f(int x) { { int x=5; return x; } } int main() { return f(3); }'
+assert_not_warning '隠しています' 'int f(int x) { { int x=5; return x; } } int main() { return f(3); }' -Wshadow -Wno-shadow
+assert_not_warning '隠しています' 'int f(int x) { int y=5; return x+y; } int main() { return f(3); }' -Wshadow
+assert_not_warning '隠しています' 'int main() { { int y=2; } int y=1; return y; }' -Wshadow
+
+assert 7 '1+2*3' --expr
+assert_emit 'li a0, 7' --expr '1+2*3'
+assert_not_emit 'mul' --expr '1+2*3'
+assert_emit 'Num(' --stop-after type --expr '1+2*3'
+assert_not_emit 'BinOp' --stop-after type --expr '1+2*3'
+assert_emit 'BinOp' --stop-after parse --expr '1+2*3'
+assert 1 '(3 < 4) + (4 <= 3) + (2 == 3) * 9' --expr
+assert 1 '7 % 3 - 10 / 10 + 1' --expr
+assert_emit 'mul' 'int main() { int x=2; return x*7; }'
+assert 8 'int main() { int x=2; return x*(1+3); }'
+assert_error '^1行目 22列目: ゼロで除算しています$' 'int main() { return 1/0; }'
+assert_error '^1行目 23列目: ゼロで除算しています$' 'int main() { return 5 % (2-2); }'
+assert_error 'ゼロで除算しています' 'int main() { int x=1; return x/0; }'
+assert_error 'ゼロで除算しています' 'int main() { int x=1; x %= 0; return x; }'
+assert_compile_status 1 'int main() { return 1/0; }'
+assert 0 '0 && 1/0' --expr
+assert 1 '1 || 1/0' --expr
+assert 2 'int main() { if (0) return 1/0; return 2; }'
+assert 3 'int main() { return 1 ? 3 : 1/0; }'
+assert_error 'ゼロで除算しています' 'int main() { if (1) return 1/0; return 2; }'
+assert 0 'int main() { int x=1; return 0 && x/0; }'
+assert_not_emit 'BinOp' --stop-after type --expr '0 && 1/0'
+assert_not_emit 'BinOp' --stop-after type --expr '2 || 1/0'
+assert_emit 'LogAnd' --stop-after type 'int main() { int x=1; return 1 && x; }'
+
+assert 1 'int main() { int x; int *p=&x; int c=1; return _Generic(c ? p : 0, int*: 1, default: 2); }'
+assert 1 'int main() { int x; int *p=&x; int c=1; return _Generic(c ? 0 : p, int*: 1, default: 2); }'
+assert 8 'int main() { int x; int *p=&x; int c=0; return sizeof(c ? p : 0); }'
+assert 1 'int main() { int x, y; int *p=&x, *q=&y; int c=1; return _Generic(c ? p : q, int*: 1, default: 2); }'
+assert 1 'int main() { int a[2]; int *p=a; int c=1; return _Generic(c ? a : p, int*: 1, default: 2); }'
+assert 4 'int main() { int c=1; return sizeof(c ? 1 : 2); }'
+assert 1 'int main() { int x=3; int *p=&x; int c=0; return (c ? p : 0) == 0; }'
+assert_warning '警告: 条件演算子でポインタと整数の型が一致しません' 'int main() { int x; int *p=&x; int c=1; c ? p : 1; return 0; }'
+assert_warning '警告: 条件演算子の2つのポインタの型が一致しません' 'int main() { int x; int *p=&x; int **q=&p; int c=1; c ? p : q; return 0; }'
+assert_not_warning '警告' 'int main() { int x; int *p=&x; int c=1; c ? p : 0; return 0; }'
+
+assert 5 'int g; int set() { g = 5; return 0; } int main() { set(); return g; }'
+assert 7 'int g; int add(int x) { g = g + x; return g; } int main() { add(3); add(4); return g; }'
+assert 0 'int g; int main() { return g; }'
+assert 3 'int arr[4]; int main() { arr[2]=3; return arr[2]; }'
+assert 16 'int arr[4]; int main() { return sizeof(arr); }'
+assert 9 'int a, *p; int main() { p=&a; *p=9; return a; }'
+assert 2 'int g; int main() { int g=2; return g; }'
+assert 6 'int a[3]; int sum() { return a[0]+a[1]+a[2]; } int main() { int i; for (i=0; i<3; i++) a[i]=i+1; return sum(); }'
+assert_emit '\.bss' 'int g; int main() { return g; }'
+assert_emit 'la a0, g' 'int g; int main() { return g; }'
+assert_not_emit '\.bss' 'int main() { return 0; }'
+assert_emit 'GlobalVar(g)' --ast-dot 'int g; int main() { return g; }'
+assert_error '変数が二重に宣言されています' 'int g; int g; int main() { return 0; }'
+assert_error 'inline は関数にしか指定できません' 'inline int g; int main() { return 0; }'
+
+assert 104 'int main() { return "hello"[0]; }'
+assert 111 'int main() { char *s = "hello"; return s[4]; }'
+assert 0 'int main() { return "hello"[5]; }'
+assert 6 'int main() { return sizeof("hello"); }'
+assert 1 'int main() { return sizeof("hello"[0]); }'
+assert 10 'int main() { return "a\tb\n"[3]; }'
+assert 9 'int main() { return "a\tb\n"[1]; }'
+assert 92 'int main() { return "\\"[0]; }'
+assert 34 'int main() { return "\""[0]; }'
+assert 2 'int main() { return sizeof("\n"); }'
+assert 8 'int main() { return sizeof(&"abc"[0]); }'
+assert 1 'int main() { char c; return sizeof(c); }'
+assert 3 'int main() { char c = 3; return c; }'
+assert 7 'int main() { char a[3]; a[0]=3; a[1]=4; return a[0]+a[1]; }'
+assert 4 'int main() { char c = 1; return sizeof(c + 1); }'
+assert 1 'int main() { char c = 1; return sizeof(c = 2); }'
+assert 5 'int main() { char c = 2; c += 3; return c; }'
+assert 0 'int main() { char c = 256; return c; }'
+assert 3 'int f(char a, char b) { return a + b; } int main() { return f(1, 2); }'
+assert 2 'char *g; int main() { g = "xyz"; return g[2] - g[0]; }'
+assert_stdout 'hi' 'int main() { char *s = "hi"; __builtin_putchar(s[0]); __builtin_putchar(s[1]); return 0; }'
+assert_emit '^  \.string "hello"$' 'int main() { return "hello"[0]; }'
+assert_emit '^  \.string "a\\tb\\n"$' 'int main() { return "a\tb\n"[0]; }'
+assert_emit '^  \.string "\\\\\\""$' 'int main() { return "\\\""[0]; }'
+assert_emit '^\.L\.str\.1:$' 'int main() { "a"; "b"; return 0; }'
+assert_emit 'lb a0, 0(a0)' 'int main() { char c = 3; return c; }'
+assert_error '^1行目 21列目: 文字列リテラルが閉じられていません$' 'int main() { return "oops; }'
+assert_error '^2行目 10列目: 文字列リテラルが閉じられていません$' 'int main() {
+  return "oops
+; }'
+
+assert 1 '5 & 3' --expr
+assert 7 '5 | 3' --expr
+assert 6 '5 ^ 3' --expr
+assert 40 '5 << 3' --expr
+assert 5 '40 >> 3' --expr
+assert 250 '~5' --expr
+assert 255 '~0' --expr
+assert 252 '-16 >> 2' --expr
+assert 32 '1 << 2 + 3' --expr
+assert 1 '5 & 3 == 3' --expr
+assert 3 '1 | 2 ^ 3 & 1' --expr
+assert 1 '1 << 3 > 7' --expr
+assert 1 '1 | 0 && 2' --expr
+assert 1 'int main() { int a=12, b=10; return (a & b) == 8 && (a | b) == 14 && (a ^ b) == 6; }'
+assert 48 'int main() { int a=3, b=4; return a << b; }'
+assert 3 'int main() { int a=48, b=4; return a >> b; }'
+assert 250 'int main() { int a=5; return ~a; }'
+assert 10 'int main() { char c=5; return c << 1; }'
+assert_emit 'and a0, t0, t1' 'int main() { int a=1, b=2; return a & b; }'
+assert_emit 'or a0, t0, t1' 'int main() { int a=1, b=2; return a | b; }'
+assert_emit 'xor a0, t0, t1' 'int main() { int a=1, b=2; return a ^ b; }'
+assert_emit 'sll a0, t0, t1' 'int main() { int a=1, b=2; return a << b; }'
+assert_emit 'sra a0, t0, t1' 'int main() { int a=1, b=2; return a >> b; }'
+assert_emit 'not a0, a0' 'int main() { int a=1; return ~a; }'
+assert_emit 'BitNot' --ast-dot --expr '~1'
+assert_error 'ビット演算のオペランドは整数でなければなりません' 'int main() { int x; return &x & 1; }'
+assert_error 'ビット演算のオペランドは整数でなければなりません' 'int main() { int x; return ~&x; }'
+
+assert 16 'int main() { int x=2; return x * 8; }'
+assert 6 'int main() { int x=2; return x * 3; }'
+assert 45 'int main() { int x=5; return x * 9; }'
+assert 10 'int main() { int x=2; return 5 * x; }'
+assert 14 'int main() { int x=2; return x * 7; }'
+assert 254 'int main() { int x=-2; return x * 1; }'
+assert 240 'int main() { int x=-2; return x * 8; }'
+assert 3 'int main() { int a[4]; a[3]=3; int *p=a; return *(p + 3); }'
+assert_emit 'slli a0, a0, 3' 'int main() { int x=2; return x * 8; }'
+assert_not_emit 'mul' 'int main() { int x=2; return x * 8; }'
+assert_emit 'slli t0, a0, 1' 'int main() { int x=2; return x * 3; }'
+assert_emit 'add a0, t0, a0' 'int main() { int x=2; return x * 3; }'
+assert_emit 'mul a0, t0, t1' 'int main() { int x=2; return x * 7; }'
+
+assert 1 'int main() { int i=0; do { i=i+1; } while (0); return i; }'
+assert 10 'int main() { int i=0; do i=i+1; while (i<10); return i; }'
+assert 1 'int main() { int i=0; do { i++; } while (i > 5); return i; }'
+assert 3 'int main() { int i=0; do { i++; if (i==3) break; } while (1); return i; }'
+assert 5 'int main() { int i=0, n=0; do { i++; if (i%2) continue; n++; } while (i<10); return n; }'
+assert 6 'int main() { int i=0, n=0; do { int j=0; do { j++; n++; } while (j<2); i++; } while (i<3); return n; }'
+assert_emit 'DoWhile' --ast-dot 'int main() { do ; while (0); return 0; }'
+assert_error "'while' が必要ですが" 'int main() { do ; return 0; }'
+assert_error "';' が必要ですが" 'int main() { do ; while (0) return 0; }'
+
+assert 0 'int main() { int x, d=10, n=0; for (x=-3000; x<=3000; x=x+7) if (x / 10 != x / d) n++; return n; }'
+assert 0 'int main() { int x, d=3, n=0; for (x=-3000; x<=3000; x=x+7) if (x / 3 != x / d) n++; return n; }'
+assert 0 'int main() { int x, d=7, n=0; for (x=-3000; x<=3000; x=x+7) if (x / 7 != x / d) n++; return n; }'
+assert 0 'int main() { int x, d=-7, n=0; for (x=-3000; x<=3000; x=x+7) if (x / -7 != x / d) n++; return n; }'
+assert 0 'int main() { int x, d=2, n=0; for (x=-3000; x<=3000; x=x+7) if (x / 2 != x / d) n++; return n; }'
+assert 0 'int main() { int x, d=16, n=0; for (x=-3000; x<=3000; x=x+7) if (x / 16 != x / d) n++; return n; }'
+assert 0 'int main() { int x, d=-1000, n=0; for (x=-3000; x<=3000; x=x+7) if (x / -1000 != x / d) n++; return n; }'
+assert 0 'int main() { int x, d=641, n=0; for (x=-3000; x<=3000; x=x+7) if (x / 641 != x / d) n++; return n; }'
+assert 0 'int main() { int x, d=2147483647, n=0; for (x=-3000; x<=3000; x=x+7) if (x / 2147483647 != x / d) n++; return n; }'
+assert 9 'int main() { int x=95; return x / 10; }'
+assert 247 'int main() { int x=-95; return x / 10; }'
+assert 9 'int main() { int x=-95; return x / -10; }'
+assert 1 'int main() { int x=2147483647; return x / 2147483647; }'
+assert 5 'int main() { int x=5; return x / 1; }'
+assert 3 'int main() { int a[4]; return &a[3] - &a[0]; }'
+assert_emit 'mulh t1, a0, t1' 'int main() { int x=95; return x / 10; }'
+assert_emit 'srai t1, t1, 2' 'int main() { int x=95; return x / 10; }'
+assert_not_emit 'div' 'int main() { int x=95; return x / 10; }'
+assert_emit 'div a0, t0, t1' 'int main() { int x=95, d=10; return x / d; }'
+assert_emit 'rem a0, t0, t1' 'int main() { int x=95; return x % 10; }'
+
+assert 1 'int main() { int a=2, b=3, r; r = a && b; return r; }'
+assert 0 'int main() { int a=2, b=0, r; r = a && b; return r; }'
+assert 1 'int main() { int a=0, b=5, r; r = a || b; return r; }'
+assert 0 'int main() { int a=0, b=0, r; r = a || b; return r; }'
+assert 2 'int main() { int a=-1, b=7; int r = a && b; return r + r; }'
+assert 11 'int main() { int n=0; int r = (n++, 4) && 2; return n*10 + r; }'
+assert 10 'int main() { int n=0; int r = (n++, 0) && n++; return n*10 + r; }'
+assert 1 'int g; int main() { int a=3; g = a || 0; return g; }'
+assert_emit_next '^\.L\.short\.1:$' '^  li a0, 0$' 'int main() { int a=2, b=3, r; r = a && b; return r; }'
+assert_emit_next '^  li a0, 0$' '^\.L\.end\.1:$' 'int main() { int a=2, b=3, r; r = a && b; return r; }'
+assert_emit_next '^\.L\.end\.1:$' '^  # push a0$' 'int main() { int a=2, b=3, r; r = a && b; return r; }'
+assert_emit_next '^\.L\.short\.1:$' '^  li a0, 1$' 'int main() { int a=2, b=3, r; r = a || b; return r; }'
+
+assert 0 'int main() { int a[1000]; return 0; }'
+assert 7 'int main() { int a[1000]; a[999]=7; return a[999]; }'
+assert 9 'int main() { int x=4; int a[1000]; int y=5; a[0]=1; return x+y; }'
+assert 0 'int main() { int a[1000]; int x; return x; }' -ftrivial-auto-var-init=zero
+assert_emit 'li t0, 4000' 'int main() { int a[1000]; return 0; }'
+assert_emit 'sub sp, sp, t0' 'int main() { int a[1000]; return 0; }'
+assert_emit 'add a0, fp, t0' 'int main() { int a[1000]; a[0]=1; return a[0]; }'
+assert_not_emit 'addi sp, sp, -4000' 'int main() { int a[1000]; return 0; }'
+assert 0 'int f(int a[3]) { return 0; } int main() { return f(0); }'
+assert 7 'int f(int a[3]) { return a[1]; } int main() { int x[3]; x[1]=7; return f(x); }'
+assert 8 'int f(int a[3]) { return sizeof(a); } int main() { int x[3]; return f(x); }'
+assert 3 'int f(int a[2][3]) { return a[1][2]; } int main() { int x[2][3]; x[1][2]=3; return f(x); }'
+
+assert 1 'int main() { char c; return (c = 200) == -56; }'
+assert 1 'int main() { char c = 127; return (c += 1) == -128; }'
+assert 1 'int main() { char c = -128; return (c -= 1) == 127; }'
+assert 1 'int main() { char c = 127; return c++ == 127 && c == -128; }'
+assert 1 'int main() { char c = 127; return ++c == -128; }'
+assert 1 'int main() { char c; int x = (c = 300); return x == 44; }'
+assert_emit 'lb a0, 0(t1)' 'int main() { char c; c = 1; return 0; }'
+
+assert 1 'int main() { char c; return sizeof((0, c)); }'
+assert 4 'int main() { char c; return sizeof((c, 0)); }'
+assert 8 'int main() { char a[4]; return sizeof((0, a)); }'
+assert 1 'int main() { char c = 200; return (0, c) == -56; }'
+
+assert 0 'int main() { return "\0"[0]; }'
+assert 2 'int main() { return sizeof("\0"); }'
+assert 98 'int main() { return "a\0b"[2]; }'
+assert 4 'int main() { return sizeof("a\0b"); }'
+assert 65 'int main() { return "\101"[0]; }'
+assert 52 'int main() { return "\1234"[1]; }'
+assert 3 'int main() { return sizeof("\1234"); }'
+assert 255 'int main() { return "\377"[0]; }'
+assert 7 'int main() { return "\a"[0]; }'
+assert 63 'int main() { return "\?"[0]; }'
+assert 3 'int main() { return sizeof("é"); }'
+assert_emit '^  \.string "a\\000b"$' 'int main() { return "a\0b"[0]; }'
+assert_emit '^  \.string "\\377"$' 'int main() { return "\377"[0]; }'
+assert_error '^1行目 22列目: 不明なエスケープシーケンスです$' 'int main() { return "\q"[0]; }'
+assert_error '^1行目 22列目: 八進エスケープが範囲外です$' 'int main() { return "\777"[0]; }'
+
+assert 65 'int main() { return "\x41"[0]; }'
+assert 255 'int main() { return "\xff"[0]; }'
+assert 10 'int main() { return "\xA"[0]; }'
+assert 103 'int main() { return "\x41g"[1]; }'
+assert 3 'int main() { return sizeof("\x41g"); }'
+assert 0 'int main() { return "\x00"[0]; }'
+assert_emit '^  \.string "\\377"$' 'int main() { return "\xff"[0]; }'
+assert_error '^1行目 22列目: 十六進エスケープに数字がありません$' 'int main() { return "\xg"[0]; }'
+assert_error '^1行目 22列目: 十六進エスケープが範囲外です$' 'int main() { return "\x100"[0]; }'
+assert_error '十六進エスケープが範囲外です' 'int main() { return "\xfffffffffffff"[0]; }'
+
+assert 8 "0'10" --expr
+assert 83 "0'1'2'3" --expr
+assert 8 "010" --expr
+assert_error '数字区切りの位置が正しくありません' "int main() { return 0'8; }"
+assert_error '数字区切りの位置が正しくありません' "int main() { return 0''1; }"
+
+assert_error '^1行目 33列目: ポインタは乗除算のオペランドにできません$' 'int main() { int x=1; return &x * 2; }'
+assert_error '^                                \^$' 'int main() { int x=1; return &x * 2; }'
+assert_error '^1行目 16列目: 左辺値が必要です$' 'int main() { 5 = 1; return 0; }'
+assert_error '^1行目 30列目: 演算子のオペランドの型が正しくありません$' 'int main() { int x; return 1 - &x; }'
+assert_compile_status 1 'int main() { int x; return 1 - &x; }'
+assert_compile_status 1 'int main() { int x; int *p=&x; p += p; return 0; }'
+assert_error '^1行目 26列目: 関数へのポインタは加減算できません$' 'int main() { return main + 1; }'
+assert_error '^1行目 27列目: 関数型の変数は宣言できません$' 'int main() { typeof(main) x; return 0; }'
+assert_error '関数に sizeof は適用できません' 'int main() { return sizeof(typeof(main)); }'
+assert_error '_Generic に Int に一致する型がありません' 'int main() { return _Generic(1, char: 2); }'
+
+assert 16 'int main() { int buf[sizeof(int)]; return sizeof(buf); }'
+assert 64 'int main() { int buf[sizeof(int)*4]; return sizeof(buf); }'
+assert 24 'int main() { int a[3]; char b[sizeof(a)*2]; return sizeof(b); }'
+assert 12 'int main() { int a[1+2]; return sizeof(a); }'
+assert 4 'int main() { int a[2 ? 1 : 0]; return sizeof(a); }'
+assert 20 'int g[10/2]; int main() { return sizeof(g); }'
+assert 4 'int main() { return sizeof(1/0); }'
+assert_error '^1行目 29列目: 配列の長さは 0 以上の定数でなければなりません$' 'int main() { int n=2; int a[n]; return 0; }'
+assert_error '配列の長さは 0 以上の定数でなければなりません' 'int main() { int a[-1]; return 0; }'
+assert_error 'ゼロで除算しています' 'int main() { int a[1/0]; return 0; }'
+
+assert 200 'int main() { unsigned char c=200; return c; }'
+assert 1 'int main() { unsigned char c=200; return c == 200; }'
+assert 1 'int main() { signed char c=200; return c == -56; }'
+assert 1 'int main() { char c=200; return c == -56; }'
+assert 1 'int main() { unsigned char c=255; c++; return c == 0; }'
+assert 1 'int main() { unsigned char c=255; return c++ == 255; }'
+assert 1 'int main() { unsigned char c=0; c -= 1; return c == 255; }'
+assert 1 'int main() { unsigned char a[2]; a[1]=250; return a[1] > 200; }'
+assert 1 'int main() { unsigned char c; return sizeof(c); }'
+assert 4 'int main() { unsigned char c; return sizeof(+c); }'
+assert 1 'int main() { unsigned char c; return _Generic(c, unsigned char: 1, char: 2); }'
+assert 2 'int main() { signed char c; return _Generic(c, unsigned char: 1, char: 2); }'
+assert 4 'int main() { signed x=4; return x; }'
+assert 4 'int main() { signed int x; return sizeof(x); }'
+assert 1 'int main() { return sizeof(unsigned char); }'
+assert_emit 'lbu a0, 0(a0)' 'int main() { unsigned char c=1; return c; }'
+assert_not_emit 'lbu' 'int main() { signed char c=1; return c; }'
+assert_error "'char' が必要ですが" 'int main() { unsigned x; return 0; }'
+
+for source in golden/*.c; do
+  assert_golden "$source"
+done
 
 echo OK